@@ -0,0 +1,80 @@
+//! A minimal decoder for the standard base64 alphabet.
+
+use std::{error::Error, fmt};
+
+/// An error returned when a value isn't valid base64.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The value contains a character outside of the base64
+    /// alphabet (or padding in the middle of the value).
+    InvalidCharacter {
+        /// The invalid character.
+        character: char,
+        /// The byte index of the character in the value.
+        index: usize,
+    },
+    /// The value has a length that no base64 encoding can have,
+    /// or incorrect padding.
+    InvalidLength,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidCharacter { character, index } => write!(
+                f,
+                "invalid base64 character {:?} at index {}",
+                character, index
+            ),
+            DecodeError::InvalidLength => write!(f, "invalid base64 length"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+fn sextet(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// Decode the given base64 value (standard alphabet).
+///
+/// Padding is optional, but when present it must bring the length
+/// of the value to a multiple of 4.
+pub(crate) fn decode(value: &str) -> Result<Vec<u8>, DecodeError> {
+    let bytes = value.as_bytes();
+    let data = value.trim_end_matches('=').as_bytes();
+    let padding = bytes.len() - data.len();
+
+    if padding > 2 || (padding > 0 && !bytes.len().is_multiple_of(4)) || data.len() % 4 == 1 {
+        return Err(DecodeError::InvalidLength);
+    }
+
+    let mut decoded = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for (index, &c) in data.iter().enumerate() {
+        let Some(sextet) = sextet(c) else {
+            return Err(DecodeError::InvalidCharacter {
+                character: value[index..].chars().next().unwrap_or_default(),
+                index,
+            });
+        };
+
+        buffer = (buffer << 6) | sextet as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+
+    Ok(decoded)
+}
@@ -0,0 +1,42 @@
+//! A minimal parser for a single CSV record.
+
+/// Split the given CSV record into fields.
+///
+/// Fields are separated by commas. A field starting with a double
+/// quote is quoted: it can contain commas, and a doubled quote
+/// (`""`) inside of it stands for a single quote. Characters
+/// following the closing quote of a field are kept as is, and an
+/// unterminated quoted field extends to the end of the record.
+/// Whitespace is never trimmed and newlines have no special
+/// meaning.
+pub(crate) fn split_record(record: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+
+    let mut chars = record.chars().peekable();
+    let mut field_start = true;
+    let mut in_quotes = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if field_start => in_quotes = true,
+            ',' if !in_quotes => {
+                fields.push(std::mem::take(&mut field));
+                field_start = true;
+                continue;
+            }
+            c => field.push(c),
+        }
+        field_start = false;
+    }
+    fields.push(field);
+
+    fields
+}
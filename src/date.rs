@@ -0,0 +1,77 @@
+//! A minimal parser for `YYYY-MM-DD` dates.
+
+use std::{error::Error, fmt};
+
+/// An error returned when a value isn't a valid `YYYY-MM-DD` date.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DateParseError {
+    /// The value doesn't have the `YYYY-MM-DD` format.
+    InvalidFormat,
+    /// The value has the right format but isn't a date of the
+    /// calendar (like `2023-02-29`).
+    InvalidDate,
+}
+
+impl fmt::Display for DateParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateParseError::InvalidFormat => write!(f, "expected a date like YYYY-MM-DD"),
+            DateParseError::InvalidDate => write!(f, "no such date"),
+        }
+    }
+}
+
+impl Error for DateParseError {}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Get the number of days since 1970-01-01 of the given date of
+/// the proleptic Gregorian calendar.
+///
+/// See <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Parse the given `YYYY-MM-DD` date into the Unix timestamp of its
+/// midnight UTC.
+pub(crate) fn parse_ymd(value: &str) -> Result<i64, DateParseError> {
+    let bytes = value.as_bytes();
+    let well_formed = bytes.len() == 10
+        && bytes[4] == b'-'
+        && bytes[7] == b'-'
+        && bytes
+            .iter()
+            .enumerate()
+            .all(|(i, b)| i == 4 || i == 7 || b.is_ascii_digit());
+    if !well_formed {
+        return Err(DateParseError::InvalidFormat);
+    }
+
+    let number = |range: std::ops::Range<usize>| value[range].parse::<i64>();
+    let (Ok(year), Ok(month), Ok(day)) = (number(0..4), number(5..7), number(8..10)) else {
+        return Err(DateParseError::InvalidFormat);
+    };
+
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return Err(DateParseError::InvalidDate);
+    }
+
+    Ok(days_from_civil(year, month, day) * 86400)
+}
@@ -1,6 +1,27 @@
-use std::{collections::HashMap, env};
+//! A simple cli argument parser.
 
-///! A simple cli argument parser.
+#[cfg(feature = "base64")]
+mod base64;
+mod csv;
+mod date;
+mod shell;
+
+#[cfg(feature = "base64")]
+pub use base64::DecodeError;
+pub use date::DateParseError;
+
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    ffi::OsString,
+    fmt,
+    io::{self, BufRead, Read, Write},
+    iter,
+    path::PathBuf,
+    str::FromStr,
+    sync::OnceLock,
+};
 
 /// Build the [`Args`] object associated with the arguments
 /// that the program was started with.
@@ -17,6 +38,825 @@ pub fn parse() -> Args {
     Args::parse_raw(&env::args().collect::<Vec<_>>())
 }
 
+/// Build the [`Args`] object associated with the arguments
+/// that the program was started with, failing on malformed
+/// options instead of silently accepting them.
+///
+/// The following option tokens are considered malformed
+/// ([`ParseError::MalformedOption`]):
+///
+/// - a bare `--` (missing option name),
+/// - an option starting with three or more dashes (`---foo`),
+/// - an option with an empty name before an attached value
+///   (`--=value`).
+///
+/// Option names containing whitespace, which can only be given
+/// by quoting them (`"--foo bar"`), are rejected as well
+/// ([`ParseError::InvalidOptionName`]).
+///
+/// A bare `-` isn't an option: it commonly stands for stdin or
+/// stdout and is kept as a positional argument.
+///
+/// [`parse`] accepts these leniently: `---foo` is kept as an
+/// option named `-foo` and names containing whitespace are kept
+/// as is, while `--` and `--=value` are ignored.
+///
+/// ```
+/// # fn main() {
+/// match valargs::try_parse() {
+///     Ok(args) => println!("first argument: {:?}", args.nth(1)),
+///     Err(e) => eprintln!("error: {}", e),
+/// }
+/// # }
+/// ```
+pub fn try_parse() -> Result<Args, ParseError> {
+    Args::try_parse_raw(&env::args().collect::<Vec<_>>())
+}
+
+/// Build the [`Args`] object associated with the arguments
+/// that the program was started with, using the given
+/// [`ParseOptions`].
+///
+/// ```
+/// # fn main() {
+/// use valargs::ParseOptions;
+///
+/// let args = valargs::parse_with(&ParseOptions::new().default_value("color", "auto"));
+/// if let Some(color) = args.option_value("color") {
+///     println!("color mode: {}", color);
+/// }
+/// # }
+/// ```
+pub fn parse_with(parse_options: &ParseOptions) -> Args {
+    Args::parse_raw_with(&env::args().collect::<Vec<_>>(), parse_options)
+}
+
+/// Build the [`Args`] object associated with the arguments
+/// that the program was started with, prepended with the
+/// arguments contained in the given environment variable.
+///
+/// The contents of the variable are split following shell-like
+/// quoting rules and inserted right after the executable name,
+/// so options given on the command line override the ones from
+/// the variable. An unset or empty variable is a no-op.
+///
+/// ```
+/// # fn main() -> Result<(), valargs::ParseError> {
+/// // with MYTOOL_ARGS="--color never", `mytool --color always`
+/// // ends up with color set to "always"
+/// let args = valargs::parse_with_env_args("MYTOOL_ARGS")?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_with_env_args(var_name: &str) -> Result<Args, ParseError> {
+    parse_with_env_args_with(var_name, &ParseOptions::default())
+}
+
+/// Build the [`Args`] object associated with the arguments
+/// that the program was started with, prepended with the
+/// arguments contained in the given environment variable, using
+/// the given [`ParseOptions`] for both.
+///
+/// See [`parse_with_env_args`] for how the variable is handled.
+///
+/// ```
+/// # fn main() -> Result<(), valargs::ParseError> {
+/// use valargs::ParseOptions;
+///
+/// // with MYTOOL_ARGS="--jobs:4", `mytool --color:always`
+/// let parse_options = ParseOptions::new().value_separators(&[':']);
+/// let args = valargs::parse_with_env_args_with("MYTOOL_ARGS", &parse_options)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_with_env_args_with(
+    var_name: &str,
+    parse_options: &ParseOptions,
+) -> Result<Args, ParseError> {
+    parse_with_env_args_from(
+        &env::args().collect::<Vec<_>>(),
+        var_name,
+        parse_options,
+        |name| env::var(name).ok(),
+    )
+}
+
+fn parse_with_env_args_from(
+    raw_args: &[String],
+    var_name: &str,
+    parse_options: &ParseOptions,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Args, ParseError> {
+    let args = Args::parse_raw_with(raw_args, parse_options);
+    match lookup(var_name) {
+        Some(value) => args.prepend(&value, Some(var_name), parse_options, false),
+        None => Ok(args),
+    }
+}
+
+static GLOBAL: OnceLock<Args> = OnceLock::new();
+
+/// Get the process-wide [`Args`] object, parsing the arguments
+/// that the program was started with on first access unless
+/// [`init`] was called before.
+///
+/// ```
+/// # fn main() {
+/// if valargs::global().has_option("verbose") {
+///     println!("verbose mode");
+/// }
+/// # }
+/// ```
+pub fn global() -> &'static Args {
+    global_in(&GLOBAL)
+}
+
+/// Set the process-wide [`Args`] object returned by [`global`],
+/// typically parsed with a custom configuration in `main`.
+///
+/// Fails if the process-wide object was already set, either by a
+/// previous call to `init` or because [`global`] was called first
+/// and parsed the arguments itself.
+///
+/// ```
+/// # fn main() {
+/// use valargs::ParseOptions;
+///
+/// let args = valargs::parse_with(&ParseOptions::new().default_value("jobs", "1"));
+/// valargs::init(args).expect("arguments already initialized");
+///
+/// let jobs = valargs::global().option_value("jobs");
+/// # }
+/// ```
+pub fn init(args: Args) -> Result<(), AlreadyInitialized> {
+    init_in(&GLOBAL, args)
+}
+
+fn global_in(cell: &OnceLock<Args>) -> &Args {
+    cell.get_or_init(parse)
+}
+
+fn init_in(cell: &OnceLock<Args>, args: Args) -> Result<(), AlreadyInitialized> {
+    cell.set(args).map_err(|_| AlreadyInitialized)
+}
+
+/// An error returned by [`init`] when the process-wide [`Args`]
+/// object was already set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlreadyInitialized;
+
+impl fmt::Display for AlreadyInitialized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the global arguments were already initialized")
+    }
+}
+
+impl Error for AlreadyInitialized {}
+
+/// Build the [`Args`] object associated with the arguments
+/// that the program was started with, as a cargo subcommand
+/// plugin named `cargo-<name>`.
+///
+/// See [`Args::strip_cargo_prefix`].
+///
+/// ```
+/// # fn main() {
+/// // both `cargo foo build` and `cargo-foo build` give "build"
+/// let args = valargs::parse_cargo_subcommand("foo");
+/// println!("{:?}", args.nth(1));
+/// # }
+/// ```
+pub fn parse_cargo_subcommand(name: &str) -> Args {
+    parse().strip_cargo_prefix(name)
+}
+
+/// The names of the options used to compute a verbosity level,
+/// see [`Args::verbosity_with`].
+///
+/// By default, `-v`/`--verbose` increase the level and
+/// `-q`/`--quiet` decrease it.
+#[derive(Debug, Clone)]
+pub struct VerbosityConfig {
+    verbose: (char, String),
+    quiet: (char, String),
+}
+
+impl VerbosityConfig {
+    /// Create a new [`VerbosityConfig`] with the default names.
+    pub fn new() -> VerbosityConfig {
+        VerbosityConfig::default()
+    }
+
+    /// Set the short and long names of the option increasing the
+    /// verbosity level.
+    pub fn verbose(mut self, short: char, long: &str) -> VerbosityConfig {
+        self.verbose = (short, long.to_string());
+        self
+    }
+
+    /// Set the short and long names of the option decreasing the
+    /// verbosity level.
+    pub fn quiet(mut self, short: char, long: &str) -> VerbosityConfig {
+        self.quiet = (short, long.to_string());
+        self
+    }
+}
+
+impl Default for VerbosityConfig {
+    fn default() -> VerbosityConfig {
+        VerbosityConfig {
+            verbose: ('v', "verbose".to_string()),
+            quiet: ('q', "quiet".to_string()),
+        }
+    }
+}
+
+/// Where the value of an option came from, see
+/// [`Args::option_source`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// The option was given on the command line.
+    CommandLine {
+        /// The index of the option token in the arguments
+        /// (including the executable name).
+        argv_index: usize,
+    },
+    /// The option came from an environment variable, see
+    /// [`parse_with_env_args`].
+    Env {
+        /// The name of the environment variable.
+        var: String,
+    },
+    /// The option wasn't supplied and got its default value from
+    /// [`ParseOptions::default_value`].
+    ConfigDefault,
+}
+
+/// An error that occurred while parsing arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// A quote was opened but never closed while splitting a
+    /// string into arguments.
+    UnterminatedQuote {
+        /// The environment variable the string came from, if any.
+        var: Option<String>,
+    },
+    /// A string ended with a backslash that had nothing to
+    /// escape while splitting it into arguments.
+    TrailingBackslash {
+        /// The environment variable the string came from, if any.
+        var: Option<String>,
+    },
+    /// An option token doesn't look like a valid option, see
+    /// [`try_parse`] for the shapes that are rejected.
+    MalformedOption {
+        /// The malformed token.
+        token: String,
+        /// The index of the token in the arguments (including
+        /// the executable name).
+        index: usize,
+    },
+    /// An option name contains whitespace, see [`try_parse`].
+    InvalidOptionName {
+        /// The invalid option name.
+        name: String,
+        /// The index of the option token in the arguments
+        /// (including the executable name).
+        index: usize,
+    },
+}
+
+impl ParseError {
+    fn with_var(self, var_name: &str) -> ParseError {
+        let var = Some(var_name.to_string());
+        match self {
+            ParseError::UnterminatedQuote { .. } => ParseError::UnterminatedQuote { var },
+            ParseError::TrailingBackslash { .. } => ParseError::TrailingBackslash { var },
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (message, var) = match self {
+            ParseError::UnterminatedQuote { var } => ("unterminated quote", var),
+            ParseError::TrailingBackslash { var } => ("trailing backslash", var),
+            ParseError::MalformedOption { token, index } => {
+                let problem = if token.starts_with("---") {
+                    "too many leading dashes"
+                } else if token.trim_start_matches('-').is_empty() {
+                    "missing option name"
+                } else {
+                    "missing option name before the attached value"
+                };
+                return write!(
+                    f,
+                    "malformed option `{}` at position {}: {}",
+                    token, index, problem
+                );
+            }
+            ParseError::InvalidOptionName { name, index } => {
+                return write!(
+                    f,
+                    "invalid option name `{}` at position {}: option names can't contain whitespace",
+                    name, index
+                );
+            }
+        };
+        match var {
+            Some(var) => write!(f, "{} in environment variable {}", message, var),
+            None => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// A non-fatal issue encountered while parsing arguments, see
+/// [`Args::parse_with_warnings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// An option token doesn't look like a valid option (like a
+    /// bare `--`), see [`try_parse`] for the shapes that are
+    /// reported and how they are handled.
+    MalformedOption {
+        /// The malformed token.
+        token: String,
+        /// The index of the token in the arguments (including
+        /// the executable name).
+        index: usize,
+    },
+    /// An option name contains whitespace, see [`try_parse`].
+    InvalidOptionName {
+        /// The invalid option name.
+        name: String,
+        /// The index of the option token in the arguments.
+        index: usize,
+    },
+    /// The value of an option looks like an option itself
+    /// (`--output=--verbose`).
+    ValueLooksLikeOption {
+        /// The name of the option.
+        option: String,
+        /// The value of the option.
+        value: String,
+        /// The index of the option token in the arguments.
+        index: usize,
+    },
+    /// An option with a value is given again later, overwriting
+    /// the previous value.
+    OptionOverwritten {
+        /// The name of the option.
+        option: String,
+        /// The index of the overwriting option token in the
+        /// arguments.
+        index: usize,
+    },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::MalformedOption { token, index } => {
+                let error = ParseError::MalformedOption {
+                    token: token.to_owned(),
+                    index: *index,
+                };
+                error.fmt(f)
+            }
+            Warning::InvalidOptionName { name, index } => {
+                let error = ParseError::InvalidOptionName {
+                    name: name.to_owned(),
+                    index: *index,
+                };
+                error.fmt(f)
+            }
+            Warning::ValueLooksLikeOption {
+                option,
+                value,
+                index,
+            } => write!(
+                f,
+                "value `{}` of option {} at position {} looks like an option",
+                value,
+                dashed(option),
+                index
+            ),
+            Warning::OptionOverwritten { option, index } => write!(
+                f,
+                "option {} at position {} overwrites a previous value",
+                dashed(option),
+                index
+            ),
+        }
+    }
+}
+
+/// An error returned when more than one option of a mutually
+/// exclusive group is present.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictError {
+    /// The names of the conflicting options, in group order.
+    pub options: Vec<String>,
+}
+
+impl fmt::Display for ConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let options = self.options.iter().map(|o| dashed(o)).collect::<Vec<_>>();
+        write!(f, "conflicting options: {}", options.join(", "))
+    }
+}
+
+impl Error for ConflictError {}
+
+/// An error returned when a group of options that requires
+/// exactly one of them to be present isn't satisfied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupError {
+    /// None of the options of the group is present.
+    Missing {
+        /// The names of the options of the group.
+        options: Vec<String>,
+    },
+    /// More than one option of the group is present.
+    Conflict(ConflictError),
+}
+
+impl fmt::Display for GroupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupError::Missing { options } => {
+                let options = options.iter().map(|o| dashed(o)).collect::<Vec<_>>();
+                write!(f, "expected one of: {}", options.join(", "))
+            }
+            GroupError::Conflict(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for GroupError {}
+
+/// Expand the `$VAR` and `${VAR}` references contained in the given
+/// value using the given lookup function.
+///
+/// Variable names are made of ASCII letters, digits and underscores
+/// and can't start with a digit. Unknown variables expand to an empty
+/// string. A `$` that isn't followed by a variable name (or by a
+/// valid name between braces, like `${}` or `${1abc}`, or an
+/// unterminated `${`) is kept literally.
+fn expand_vars(value: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let is_name = |s: &str| {
+        !s.is_empty() && !s.starts_with(|c: char| c.is_ascii_digit()) && s.chars().all(is_name_char)
+    };
+
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(dollar) = rest.find('$') {
+        expanded.push_str(&rest[..dollar]);
+        let after = &rest[dollar + 1..];
+
+        let (name, remaining) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) if is_name(&braced[..end]) => (&braced[..end], &braced[end + 1..]),
+                _ => ("", after),
+            }
+        } else if after.starts_with(|c: char| c.is_ascii_digit()) {
+            ("", after)
+        } else {
+            let end = after.find(|c| !is_name_char(c)).unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+
+        if name.is_empty() {
+            expanded.push('$');
+        } else {
+            expanded.push_str(&lookup(name).unwrap_or_default());
+        }
+        rest = remaining;
+    }
+    expanded.push_str(rest);
+
+    expanded
+}
+
+/// Check if the given option value looks like an option itself,
+/// which usually means an option value was forgotten. A lone `-`
+/// and negative numbers don't.
+fn looks_like_option(value: &str) -> bool {
+    value
+        .strip_prefix('-')
+        .is_some_and(|rest| !rest.is_empty() && rest.parse::<f64>().is_err())
+}
+
+/// Check if the given character is an invisible Unicode format
+/// character that can change how the surrounding text is displayed:
+/// the soft hyphen, zero-width characters, bidirectional controls
+/// (like U+202E, which reverses the text after it), the word joiner
+/// and invisible operators, and the byte order mark.
+fn is_format_char(c: char) -> bool {
+    matches!(
+        c,
+        '\u{ad}'
+            | '\u{61c}'
+            | '\u{180e}'
+            | '\u{200b}'..='\u{200f}'
+            | '\u{202a}'..='\u{202e}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{206f}'
+            | '\u{feff}'
+            | '\u{fff9}'..='\u{fffb}'
+    )
+}
+
+/// Replace the control characters and the invisible format
+/// characters (see [`is_format_char`]) of the given string with
+/// their escaped representation (`\t`, `\n`, `\u{1b}`,
+/// `\u{202e}`...).
+fn escape_control(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_control() || is_format_char(c) {
+                c.escape_default().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Prefix the given option name with dashes the way it would
+/// usually be written on the command line (`-o` or `--option`).
+fn dashed(option_name: &str) -> String {
+    let dashes = if option_name.chars().count() == 1 {
+        "-"
+    } else {
+        "--"
+    };
+    format!("{}{}", dashes, option_name)
+}
+
+/// Options used to customize how arguments are parsed.
+///
+/// #### Example:
+///
+/// ```
+/// use valargs::ParseOptions;
+///
+/// let parse_options = ParseOptions::new()
+///     .default_value("jobs", "1")
+///     .default_value("color", "auto");
+///
+/// let args = valargs::parse_with(&parse_options);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    defaults: HashMap<String, String>,
+    toggles: Vec<char>,
+    greedy: Vec<String>,
+    value_separators: Vec<char>,
+    numeric_shorthand: Option<String>,
+    positional_prefix: Option<String>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            defaults: HashMap::new(),
+            toggles: Vec::new(),
+            greedy: Vec::new(),
+            value_separators: vec!['='],
+            numeric_shorthand: None,
+            positional_prefix: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Create a new [`ParseOptions`] with the default settings.
+    pub fn new() -> ParseOptions {
+        ParseOptions::default()
+    }
+
+    /// Build an [`Args`] from the given arguments (including the
+    /// executable name) using these options.
+    ///
+    /// Like [`try_parse`], this fails on malformed options and
+    /// invalid option names instead of silently accepting them.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), valargs::ParseError> {
+    /// use valargs::ParseOptions;
+    ///
+    /// let args = ParseOptions::new()
+    ///     .default_value("jobs", "1")
+    ///     .parse(["prog", "--color=never"])?;
+    /// assert_eq!(Some("never"), args.option_value("color"));
+    /// assert_eq!(Some("1"), args.option_value("jobs"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse<I, S>(&self, args: I) -> Result<Args, ParseError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Args::try_parse_raw_with(&args.into_iter().map(Into::into).collect::<Vec<_>>(), self)
+    }
+
+    /// Build an [`Args`] from the given arguments (including the
+    /// executable name) prepended with the arguments contained in
+    /// the given environment variable, using these options.
+    ///
+    /// See [`parse_with_env_args`] for how the variable is handled.
+    /// Like [`ParseOptions::parse`], this fails on malformed options
+    /// and invalid option names, whether they come from the
+    /// arguments or from the variable.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), valargs::ParseError> {
+    /// use valargs::ParseOptions;
+    ///
+    /// // with MYTOOL_ARGS="--jobs:4"
+    /// let args = ParseOptions::new()
+    ///     .value_separators(&[':'])
+    ///     .parse_with_env_args(["prog", "--color:always"], "MYTOOL_ARGS")?;
+    /// assert_eq!(Some("always"), args.option_value("color"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse_with_env_args<I, S>(&self, args: I, var_name: &str) -> Result<Args, ParseError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let args = self.parse(args)?;
+        match env::var(var_name) {
+            Ok(value) => args.prepend(&value, Some(var_name), self, true),
+            Err(_) => Ok(args),
+        }
+    }
+
+    /// Set a default value for the given option name.
+    ///
+    /// The default is inserted after parsing only if the option
+    /// wasn't supplied at all, so an explicit value always wins.
+    /// An option supplied without a value (as a flag) is
+    /// considered supplied and doesn't get the default either.
+    pub fn default_value(mut self, option_name: &str, value: &str) -> ParseOptions {
+        self.defaults
+            .insert(option_name.to_string(), value.to_string());
+        self
+    }
+
+    /// Set the single-character toggles recognized with both a `+`
+    /// prefix (enabling them) and a `-` prefix (disabling them),
+    /// the way the shell's `set` builtin works.
+    ///
+    /// Toggles can be bundled (`+ex`, `-ex`) as long as every
+    /// character of the token is a configured toggle, otherwise the
+    /// token is handled as usual: `-` tokens are options and `+`
+    /// tokens are positional arguments. Use [`Args::toggle_state`]
+    /// to read the resulting states.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// use valargs::ParseOptions;
+    ///
+    /// let args = valargs::parse_with(&ParseOptions::new().toggles(&['e', 'x']));
+    ///
+    /// if args.toggle_state('x') == Some(true) {
+    ///     println!("tracing enabled");
+    /// }
+    /// ```
+    pub fn toggles(mut self, toggles: &[char]) -> ParseOptions {
+        self.toggles = toggles.to_vec();
+        self
+    }
+
+    /// Mark the given option name as greedy: it consumes all the
+    /// following tokens up to the next option (or the end of the
+    /// arguments) and joins them with spaces to form its value.
+    ///
+    /// Any token starting with a dash ends the value, except for a
+    /// lone `-` (stdin or stdout) and the numbers accepted by
+    /// [`ParseOptions::numeric_shorthand`], which are part of it.
+    ///
+    /// `--` ends the value too, but isn't an end-of-options marker:
+    /// it is a malformed option token, so [`parse_with`] drops it
+    /// (the following tokens are read as usual) while
+    /// [`ParseOptions::parse`] rejects it with a
+    /// [`ParseError::MalformedOption`].
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// use valargs::ParseOptions;
+    ///
+    /// // `prog --message hello there world -v`
+    /// let args = valargs::parse_with(&ParseOptions::new().greedy("message"));
+    ///
+    /// if let Some(message) = args.option_value("message") {
+    ///     println!("message: {}", message); // "hello there world"
+    /// }
+    /// ```
+    pub fn greedy(mut self, option_name: &str) -> ParseOptions {
+        self.greedy.push(option_name.to_string());
+        self
+    }
+
+    /// Set the characters separating an option name from a value
+    /// attached to it (`--option=value`). Defaults to `=` only.
+    ///
+    /// Only the first separator of a token splits it, the rest is
+    /// kept in the value (`--define:key=value` gives `key=value`
+    /// with both `=` and `:` as separators). As a consequence, option
+    /// names containing a separator can't be expressed.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// use valargs::ParseOptions;
+    ///
+    /// // accepts both `--output=file.txt` and `--output:file.txt`
+    /// let args = valargs::parse_with(&ParseOptions::new().value_separators(&['=', ':']));
+    /// ```
+    pub fn value_separators(mut self, separators: &[char]) -> ParseOptions {
+        self.value_separators = separators.to_vec();
+        self
+    }
+
+    /// Parse `-<number>` tokens as the given option name with that
+    /// number as its value, like `head -5` for `head --lines 5`.
+    ///
+    /// Only single-dash tokens made of digits are affected, so `-5x`
+    /// is still handled as a regular option. Right after an option
+    /// without an attached value, such a token is instead taken as
+    /// that option's value, as a negative number (`--offset -5`).
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// use valargs::ParseOptions;
+    ///
+    /// // `head -5 file.txt`
+    /// let args = valargs::parse_with(&ParseOptions::new().numeric_shorthand("lines"));
+    ///
+    /// let lines = args.option_value("lines").unwrap_or("10");
+    /// ```
+    pub fn numeric_shorthand(mut self, option_name: &str) -> ParseOptions {
+        self.numeric_shorthand = Some(option_name.to_string());
+        self
+    }
+
+    /// Strip the given prefix from the positional arguments that
+    /// start with it: with a `+` prefix, `+file` is stored as
+    /// `file`.
+    ///
+    /// The executable name (`nth(0)`) is kept as is. Options and
+    /// their values aren't affected, and neither are toggles
+    /// configured with [`ParseOptions::toggles`].
+    pub fn positional_prefix(mut self, prefix: &str) -> ParseOptions {
+        self.positional_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Get the number of the given token if it is a numeric
+    /// shorthand (`-5`) and numeric shorthands are enabled.
+    fn parse_numeric_shorthand<'a>(&self, token: &'a str) -> Option<&'a str> {
+        self.numeric_shorthand.as_ref()?;
+        token
+            .strip_prefix('-')
+            .filter(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+    }
+
+    /// Check if the given token can be used as the value of an
+    /// option. A lone `-` is accepted since it commonly stands for
+    /// stdin or stdout.
+    fn is_value(&self, token: &str) -> bool {
+        (!token.starts_with('-') || token == "-" || self.parse_numeric_shorthand(token).is_some())
+            && self.parse_toggles(token).is_none()
+    }
+
+    /// Get the state and the characters of the given token if it is
+    /// a bundle of configured toggles.
+    fn parse_toggles<'a>(&self, token: &'a str) -> Option<(bool, &'a str)> {
+        let (state, toggles) = match token.strip_prefix('+') {
+            Some(toggles) => (true, toggles),
+            None => (false, token.strip_prefix('-')?),
+        };
+
+        (!toggles.is_empty() && toggles.chars().all(|c| self.toggles.contains(&c)))
+            .then_some((state, toggles))
+    }
+}
+
 /// A struct representing parsed command-line arguments.
 ///
 /// #### Example:
@@ -43,7 +883,12 @@ pub fn parse() -> Args {
 #[derive(Debug, Clone)]
 pub struct Args {
     args: Vec<String>,
-    options: HashMap<String, Option<String>>,
+    /// Every occurrence of each option, in order. The last one is
+    /// the value the option resolves to.
+    options: HashMap<String, Vec<Option<String>>>,
+    /// Where the resolved value of each option came from.
+    sources: HashMap<String, Source>,
+    toggles: HashMap<char, bool>,
 }
 
 impl Args {
@@ -73,7 +918,7 @@ impl Args {
     ///   }
     /// }
     /// ```
-    pub fn nth<'a>(&'a self, index: usize) -> Option<&'a str> {
+    pub fn nth(&self, index: usize) -> Option<&str> {
         self.args.get(index).map(|s| s.as_str())
     }
 
@@ -87,71 +932,2651 @@ impl Args {
     pub fn option_value<'a>(&'a self, option_name: &str) -> Option<&'a str> {
         self.options
             .get(option_name)
-            .and_then(|o| o.as_ref())
+            .and_then(|o| o.last()?.as_ref())
             .map(|s| s.as_str())
     }
 
-    fn parse_raw(raw_args: &[String]) -> Args {
-        let l = raw_args.len();
-
-        let mut args = Vec::new();
-        let mut options = HashMap::new();
-
-        let mut i = 0;
-        while i < l {
-            let token = raw_args[i].clone();
-
-            // Process the current token correctly whether it is an option
-            // (starting with "--" or "-") or an argument.
-            if let Some(stripped) = token.strip_prefix("--").or_else(|| token.strip_prefix("-")) {
-                // Check if the option has an associated value.
-                let param = raw_args
-                    .get(i + 1)
-                    .map(|s| s.to_owned())
-                    .filter(|s| !s.starts_with("-"));
+    /// Get the number of times the given option name is present.
+    pub fn option_count(&self, option_name: &str) -> usize {
+        self.options.get(option_name).map_or(0, |o| o.len())
+    }
 
-                // Skip the next token (the next iteration) if the option has
+    /// Serialize the arguments back into a canonical argv.
+    ///
+    /// The positional arguments (including the executable name)
+    /// come first, in order, followed by the options sorted by
+    /// name so that the output is deterministic across runs.
+    /// Repeated options are written once per occurrence, keeping
+    /// the order of their values.
+    ///
+    /// Single-character option names are written with a single
+    /// dash (`-o`) and longer ones with two (`--option`), with
+    /// their value attached (`--option=value`) if they have one,
+    /// so that values starting with a dash are read back as
+    /// values.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// // e.g. ["prog", "input.txt", "--color=never", "-v"]
+    /// println!("{:?}", args.to_canonical_argv());
+    /// ```
+    pub fn to_canonical_argv(&self) -> Vec<String> {
+        let mut options = self.options.iter().collect::<Vec<_>>();
+        options.sort_by_key(|(name, _)| *name);
+
+        let mut argv = self.args.clone();
+        for (name, values) in options {
+            for value in values {
+                argv.push(match value {
+                    Some(value) => format!("{}={}", dashed(name), value),
+                    None => dashed(name),
+                });
+            }
+        }
+
+        argv
+    }
+
+    /// Get the value of the given option if present, otherwise
+    /// the positional argument at the given index (including the
+    /// executable name, like [`Args::nth`]).
+    ///
+    /// The option always takes precedence over the positional
+    /// argument.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// // accepts both `prog --input file.txt` and `prog file.txt`
+    /// if let Some(input) = args.option_or_positional("input", 1) {
+    ///     println!("reading from {}", input);
+    /// }
+    /// ```
+    pub fn option_or_positional<'a>(
+        &'a self,
+        option_name: &str,
+        positional_index: usize,
+    ) -> Option<&'a str> {
+        self.option_value(option_name)
+            .or_else(|| self.nth(positional_index))
+    }
+
+    /// Build a new [`Args`] with the arguments contained in the
+    /// given string inserted right after the executable name.
+    ///
+    /// The string is split following shell-like quoting rules
+    /// (whitespace separates arguments, single and double quotes
+    /// group them, backslashes escape the next character). The
+    /// options already present in `self` take precedence over the
+    /// ones from the string, as if they appeared later on the
+    /// command line, except for the default values from
+    /// [`ParseOptions::default_value`], which only apply if the
+    /// string doesn't supply the option either.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), valargs::ParseError> {
+    /// let args = valargs::parse().prepend_str("--color never 'my file.txt'")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn prepend_str(&self, s: &str) -> Result<Args, ParseError> {
+        self.prepend(s, None, &ParseOptions::default(), false)
+    }
+
+    /// Like [`Args::prepend_str`], but the arguments contained in
+    /// the string are parsed with the given [`ParseOptions`], which
+    /// should be the ones `self` was parsed with.
+    ///
+    /// The default values of the options are left out, since they
+    /// already apply to `self`.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), valargs::ParseError> {
+    /// use valargs::ParseOptions;
+    ///
+    /// let parse_options = ParseOptions::new().value_separators(&[':']);
+    /// let args = parse_options
+    ///     .parse(["prog", "--color:always"])?
+    ///     .prepend_str_with("--jobs:4", &parse_options)?;
+    /// assert_eq!(Some("4"), args.option_value("jobs"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn prepend_str_with(
+        &self,
+        s: &str,
+        parse_options: &ParseOptions,
+    ) -> Result<Args, ParseError> {
+        self.prepend(s, None, parse_options, false)
+    }
+
+    /// Prepend the arguments contained in the given string, which
+    /// comes from the given environment variable if any, parsing
+    /// them with the given options (strictly if `strict` is set).
+    ///
+    /// Without a variable, the prepended options are considered part
+    /// of the command line and the sources of the existing options
+    /// are shifted accordingly.
+    fn prepend(
+        &self,
+        s: &str,
+        var: Option<&str>,
+        parse_options: &ParseOptions,
+        strict: bool,
+    ) -> Result<Args, ParseError> {
+        let tokens = shell::split(s).map_err(|e| match var {
+            Some(var) => e.with_var(var),
+            None => e,
+        })?;
+
+        // Parse the tokens behind a placeholder executable name so
+        // that the first one is handled like any other argument.
+        let parse_options = ParseOptions {
+            defaults: HashMap::new(),
+            ..parse_options.clone()
+        };
+        let raw_args = iter::once(String::new())
+            .chain(tokens.iter().cloned())
+            .collect::<Vec<_>>();
+        let prepended = if strict {
+            Args::try_parse_raw_with(&raw_args, &parse_options)?
+        } else {
+            Args::parse_raw_with(&raw_args, &parse_options)
+        }
+        .without_executable();
+
+        let mut args = self.args.clone();
+        let insert_at = args.len().min(1);
+        args.splice(insert_at..insert_at, prepended.args);
+
+        let shift_source = |source: &Source, shift: usize| match source {
+            Source::CommandLine { argv_index } => Source::CommandLine {
+                argv_index: argv_index + shift,
+            },
+            other => other.clone(),
+        };
+
+        let mut sources = prepended
+            .sources
+            .iter()
+            .map(|(name, source)| {
+                let source = match var {
+                    Some(var) => Source::Env {
+                        var: var.to_string(),
+                    },
+                    None => shift_source(source, insert_at),
+                };
+                (name.to_owned(), source)
+            })
+            .collect::<HashMap<_, _>>();
+        // Default values only fill in options that weren't supplied, so
+        // they give way to the prepended options.
+        let is_overridden_default = |name: &String| {
+            self.sources.get(name) == Some(&Source::ConfigDefault)
+                && prepended.options.contains_key(name)
+        };
+
+        let shift = if var.is_some() { 0 } else { tokens.len() };
+        for (name, source) in &self.sources {
+            if !is_overridden_default(name) {
+                sources.insert(name.to_owned(), shift_source(source, shift));
+            }
+        }
+
+        let mut options = prepended.options.clone();
+        for (name, values) in &self.options {
+            if is_overridden_default(name) {
+                continue;
+            }
+            options
+                .entry(name.to_owned())
+                .or_default()
+                .extend(values.iter().cloned());
+        }
+
+        let mut toggles = prepended.toggles;
+        toggles.extend(self.toggles.clone());
+
+        Ok(Args {
+            args,
+            options,
+            sources,
+            toggles,
+        })
+    }
+
+    /// Get the options that have an associated value as a map
+    /// from option name to value.
+    ///
+    /// Options given without a value (flags) are not included,
+    /// see [`Args::flags`] to get those.
+    pub fn options_as_map(&self) -> HashMap<String, String> {
+        self.options
+            .iter()
+            .filter_map(|(name, values)| Some((name.to_owned(), values.last()?.to_owned()?)))
+            .collect()
+    }
+
+    /// Get the names of the options that were given without a
+    /// value (flags).
+    ///
+    /// The order of the returned names is unspecified.
+    pub fn flags(&self) -> Vec<&str> {
+        self.options
+            .iter()
+            .filter(|(_, values)| values.last().is_some_and(|v| v.is_none()))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Get the last state of the given toggle (`true` for `+x`,
+    /// `false` for `-x`), or `None` if it wasn't mentioned.
+    ///
+    /// Toggles have to be configured with [`ParseOptions::toggles`].
+    pub fn toggle_state(&self, toggle: char) -> Option<bool> {
+        self.toggles.get(&toggle).copied()
+    }
+
+    /// Get a copy of these arguments without the executable name,
+    /// so that `nth(0)` returns what was previously `nth(1)`.
+    ///
+    /// Options are carried over unchanged, except that their
+    /// [`Source::CommandLine`] indices are shifted down by one to
+    /// match. This is useful when handing the remaining arguments
+    /// to a subcommand.
+    pub fn without_executable(&self) -> Args {
+        let mut args = self.clone();
+        if args.args.is_empty() {
+            return args;
+        }
+
+        args.args.remove(0);
+        for source in args.sources.values_mut() {
+            if let Source::CommandLine { argv_index } = source
+                && *argv_index > 0
+            {
+                *argv_index -= 1;
+            }
+        }
+
+        args
+    }
+
+    /// Check that at most one option of the given mutually
+    /// exclusive group is present.
+    ///
+    /// Returns `Ok(None)` if none of them is present, `Ok(Some(name))`
+    /// if exactly one is, and a [`ConflictError`] listing the present
+    /// ones otherwise. A name listed more than once in the group
+    /// only counts once.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// match args.check_exclusive(&["quiet", "verbose"]) {
+    ///     Ok(Some("quiet")) => println!("shh"),
+    ///     Ok(Some(_)) => println!("let's talk"),
+    ///     Ok(None) => println!("business as usual"),
+    ///     Err(e) => eprintln!("error: {}", e),
+    /// }
+    /// ```
+    pub fn check_exclusive(&self, group: &[&str]) -> Result<Option<&str>, ConflictError> {
+        let mut present = Vec::new();
+        for name in group {
+            if let Some((name, _)) = self.options.get_key_value(*name)
+                && !present.contains(&name.as_str())
+            {
+                present.push(name.as_str());
+            }
+        }
+
+        match present[..] {
+            [] => Ok(None),
+            [name] => Ok(Some(name)),
+            _ => Err(ConflictError {
+                options: present.iter().map(|s| s.to_string()).collect(),
+            }),
+        }
+    }
+
+    /// Check that exactly one option of the given mutually
+    /// exclusive group is present, and return its name.
+    ///
+    /// Fails with [`GroupError::Missing`] if none of them is present
+    /// and with [`GroupError::Conflict`] if more than one is.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// match args.require_exactly_one(&["json", "yaml"]) {
+    ///     Ok(format) => println!("output format: {}", format),
+    ///     Err(e) => eprintln!("error: {}", e),
+    /// }
+    /// ```
+    pub fn require_exactly_one(&self, group: &[&str]) -> Result<&str, GroupError> {
+        match self.check_exclusive(group) {
+            Ok(Some(name)) => Ok(name),
+            Ok(None) => Err(GroupError::Missing {
+                options: group.iter().map(|s| s.to_string()).collect(),
+            }),
+            Err(e) => Err(GroupError::Conflict(e)),
+        }
+    }
+
+    /// Get the verbosity level requested with `-v`/`--verbose` and
+    /// `-q`/`--quiet`, see [`Args::verbosity_with`].
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// let level = match args.verbosity() {
+    ///     ..=-1 => "error",
+    ///     0 => "warn",
+    ///     1 => "info",
+    ///     2 => "debug",
+    ///     3.. => "trace",
+    /// };
+    /// ```
+    pub fn verbosity(&self) -> i8 {
+        self.verbosity_with(&VerbosityConfig::default())
+    }
+
+    /// Get the verbosity level requested with the options described
+    /// by the given [`VerbosityConfig`].
+    ///
+    /// Each occurrence of the verbose option counts as +1 and each
+    /// occurrence of the quiet option as -1, so they cancel out.
+    /// Stacked short names are counted letter by letter: `-vvq` is
+    /// +1. The result is clamped to `-3..=3`.
+    pub fn verbosity_with(&self, config: &VerbosityConfig) -> i8 {
+        let (verbose_short, verbose_long) = &config.verbose;
+        let (quiet_short, quiet_long) = &config.quiet;
+
+        let mut level =
+            self.option_count(verbose_long) as i64 - self.option_count(quiet_long) as i64;
+
+        // Count the stacked short names (`-v`, `-vv`, `-vvq`...).
+        for (name, values) in &self.options {
+            if name == verbose_long || name == quiet_long {
+                continue;
+            }
+            if !name
+                .chars()
+                .all(|c| c == *verbose_short || c == *quiet_short)
+            {
+                continue;
+            }
+
+            let verbose = name.chars().filter(|c| c == verbose_short).count() as i64;
+            let quiet = name.chars().filter(|c| c == quiet_short).count() as i64;
+            level += (verbose - quiet) * values.len() as i64;
+        }
+
+        level.clamp(-3, 3) as i8
+    }
+
+    /// Get where the value of the given option came from, if the
+    /// option is present.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// use valargs::ParseOptions;
+    ///
+    /// let args = valargs::parse_with(&ParseOptions::new().default_value("timeout", "30"));
+    ///
+    /// if args.has_option("debug-config") {
+    ///     println!(
+    ///         "timeout = {:?} (from {:?})",
+    ///         args.option_value("timeout"),
+    ///         args.option_source("timeout"),
+    ///     );
+    /// }
+    /// ```
+    pub fn option_source(&self, option_name: &str) -> Option<Source> {
+        self.sources.get(option_name).cloned()
+    }
+
+    /// Get the value associated with the given option name with
+    /// its `$VAR` and `${VAR}` references expanded from the process
+    /// environment.
+    ///
+    /// Undefined (or non-unicode) variables expand to an empty
+    /// string, like in a shell. A `$` that isn't followed by a
+    /// variable name is kept as is.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// // `prog --path '$HOME/bin'`
+    /// if let Some(path) = args.option_value_expanded("path") {
+    ///     println!("installing to {}", path);
+    /// }
+    /// ```
+    pub fn option_value_expanded(&self, option_name: &str) -> Option<String> {
+        self.option_value(option_name)
+            .map(|value| expand_vars(value, |name| env::var(name).ok()))
+    }
+
+    /// Get the name of each present option once, regardless of how
+    /// many times it was repeated, sorted by name.
+    ///
+    /// Use [`Args::option_count`] to know how many times an option
+    /// was given.
+    pub fn distinct_options(&self) -> Vec<&str> {
+        let mut names = self.options.keys().map(|s| s.as_str()).collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// Get the positional arguments and the options at once.
+    ///
+    /// The positional arguments (including the executable name)
+    /// are in command-line order. The options are given once each
+    /// with their resolved value (the last one if repeated), sorted
+    /// by name.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// let (positionals, options) = args.parts();
+    /// for (name, value) in options {
+    ///     println!("{} = {:?}", name, value);
+    /// }
+    /// ```
+    pub fn parts(&self) -> (Vec<&str>, Vec<(&str, Option<&str>)>) {
+        let positionals = self.args.iter().map(|s| s.as_str()).collect();
+        let options = self
+            .distinct_options()
+            .into_iter()
+            .map(|name| (name, self.option_value(name)))
+            .collect();
+
+        (positionals, options)
+    }
+
+    /// Get the value associated with the given option name as a
+    /// path, checking that it exists.
+    ///
+    /// Returns `None` if the option has no value, `Some(Err(_))`
+    /// with [`io::ErrorKind::NotFound`] if the path doesn't exist,
+    /// or the error of the check itself if it couldn't be performed
+    /// (for example because of missing permissions).
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// match args.option_existing_path("input") {
+    ///     Some(Ok(path)) => println!("reading {}", path.display()),
+    ///     Some(Err(e)) => eprintln!("invalid input: {}", e),
+    ///     None => eprintln!("no input given"),
+    /// }
+    /// ```
+    pub fn option_existing_path(&self, option_name: &str) -> Option<Result<PathBuf, io::Error>> {
+        let path = PathBuf::from(self.option_value(option_name)?);
+        Some(match path.try_exists() {
+            Ok(true) => Ok(path),
+            Ok(false) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} does not exist", path.display()),
+            )),
+            Err(e) => Err(e),
+        })
+    }
+
+    /// Build an [`Args`] from positional arguments (including the
+    /// executable name) and options, as returned by [`Args::into_map`].
+    ///
+    /// Each value of an option becomes one occurrence, the last one
+    /// being the resolved value. An option with no values is a flag.
+    ///
+    /// Converting an [`Args`] to parts and back is lossless apart
+    /// from:
+    ///
+    /// - the original ordering of options relative to each other and
+    ///   to positional arguments,
+    /// - the valueless occurrences of options that also have values,
+    ///   which can change the resolved value: with `--color never
+    ///   --color`, [`Args::option_value`] gives `None` before the
+    ///   round trip and `Some("never")` after it,
+    /// - toggle states and option sources, which aren't part of the
+    ///   map.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use valargs::Args;
+    ///
+    /// let args = Args::from_parts(
+    ///     vec!["prog".to_string(), "input.txt".to_string()],
+    ///     HashMap::from([("jobs".to_string(), vec!["4".to_string()])]),
+    /// );
+    /// assert_eq!(Some("4"), args.option_value("jobs"));
+    /// ```
+    pub fn from_parts(positionals: Vec<String>, options: HashMap<String, Vec<String>>) -> Args {
+        let options = options
+            .into_iter()
+            .map(|(name, values)| {
+                let values = if values.is_empty() {
+                    vec![None]
+                } else {
+                    values.into_iter().map(Some).collect()
+                };
+                (name, values)
+            })
+            .collect();
+
+        Args {
+            args: positionals,
+            options,
+            sources: HashMap::new(),
+            toggles: HashMap::new(),
+        }
+    }
+
+    /// Convert the arguments into positional arguments (including
+    /// the executable name) and a map from option name to all the
+    /// values it was given, in order.
+    ///
+    /// Flags are represented with an empty `Vec`. See
+    /// [`Args::from_parts`] for the reverse conversion and what it
+    /// loses.
+    pub fn into_map(self) -> (Vec<String>, HashMap<String, Vec<String>>) {
+        let options = self
+            .options
+            .into_iter()
+            .map(|(name, values)| (name, values.into_iter().flatten().collect()))
+            .collect();
+
+        (self.args, options)
+    }
+
+    /// Borrowed variant of [`Args::into_map`].
+    pub fn to_map(&self) -> (Vec<&str>, HashMap<&str, Vec<&str>>) {
+        let positionals = self.args.iter().map(|s| s.as_str()).collect();
+        let options = self
+            .options
+            .iter()
+            .map(|(name, values)| {
+                let values = values.iter().flatten().map(|s| s.as_str()).collect();
+                (name.as_str(), values)
+            })
+            .collect();
+
+        (positionals, options)
+    }
+
+    /// Rename the given option, moving its values (and source) from
+    /// `from` to `to` and overwriting `to` if it was present.
+    ///
+    /// Returns whether a rename occurred, that is whether `from` was
+    /// present. This is useful to resolve aliases after parsing.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let mut args = valargs::parse();
+    ///
+    /// // accept `--colour` as an alias of `--color`
+    /// args.rename_option("colour", "color");
+    /// ```
+    pub fn rename_option(&mut self, from: &str, to: &str) -> bool {
+        let Some(values) = self.options.remove(from) else {
+            return false;
+        };
+        self.options.insert(to.to_string(), values);
+
+        match self.sources.remove(from) {
+            Some(source) => self.sources.insert(to.to_string(), source),
+            None => self.sources.remove(to),
+        };
+
+        true
+    }
+
+    /// Get the value associated with the given option name split
+    /// into lines.
+    ///
+    /// Lines are split on `\n` and a trailing `\r` is removed from
+    /// each of them. Empty lines are kept, except for the one that
+    /// would follow a final newline (`"a\n"` gives `["a"]`).
+    pub fn option_value_lines<'a>(&'a self, option_name: &str) -> Option<Vec<&'a str>> {
+        self.option_value(option_name).map(|value| {
+            if value.is_empty() {
+                return Vec::new();
+            }
+            value
+                .strip_suffix('\n')
+                .unwrap_or(value)
+                .split('\n')
+                .map(|line| line.strip_suffix('\r').unwrap_or(line))
+                .collect()
+        })
+    }
+
+    /// Get the user positional arguments, that is all of them
+    /// except the executable name.
+    fn user_positionals(&self) -> &[String] {
+        self.args.get(1..).unwrap_or_default()
+    }
+
+    /// Get the last positional argument, excluding the executable
+    /// name.
+    pub fn last_positional(&self) -> Option<&str> {
+        self.nth_from_end(0)
+    }
+
+    /// Get the nth positional argument counting from the end, `0`
+    /// being the last one, like Python's negative indexing.
+    ///
+    /// Unlike [`Args::nth`], the executable name isn't counted: it
+    /// is never returned, even when there are no other positional
+    /// arguments.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// // `prog a b c`
+    /// let last = args.nth_from_end(0); // Some("c")
+    /// let second_to_last = args.nth_from_end(1); // Some("b")
+    /// let out_of_range = args.nth_from_end(3); // None, not "prog"
+    /// ```
+    pub fn nth_from_end(&self, n: usize) -> Option<&str> {
+        self.user_positionals()
+            .iter()
+            .rev()
+            .nth(n)
+            .map(|s| s.as_str())
+    }
+
+    /// Split the positional arguments (excluding the executable
+    /// name) into a head and the last `k` of them.
+    ///
+    /// Returns `None` if there are fewer than `k` positional
+    /// arguments.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// // `cp SOURCE... DEST`
+    /// match args.split_last_positionals(1) {
+    ///     Some((sources, [dest])) if !sources.is_empty() => {
+    ///         for source in sources {
+    ///             println!("copying {} to {}", source, dest);
+    ///         }
+    ///     }
+    ///     _ => eprintln!("usage: cp SOURCE... DEST"),
+    /// }
+    /// ```
+    pub fn split_last_positionals(&self, k: usize) -> Option<(&[String], &[String])> {
+        let positionals = self.user_positionals();
+        let at = positionals.len().checked_sub(k)?;
+        Some(positionals.split_at(at))
+    }
+
+    /// Get the number of positional arguments (including the
+    /// executable name) plus the number of option occurrences, so
+    /// that a repeated option is counted each time.
+    ///
+    /// Option values aren't counted separately from their option,
+    /// toggles aren't counted, and options that only got a default
+    /// value from [`ParseOptions::default_value`] don't count.
+    pub fn token_count(&self) -> usize {
+        let option_occurrences = self
+            .options
+            .iter()
+            .filter(|(name, _)| self.sources.get(*name) != Some(&Source::ConfigDefault))
+            .map(|(_, values)| values.len())
+            .sum::<usize>();
+
+        self.args.len() + option_occurrences
+    }
+
+    /// Get the name the program was invoked as, that is the
+    /// executable name without its directory components (using
+    /// either `/` or `\` as separators) and without a trailing
+    /// `.exe`.
+    ///
+    /// Other extensions are kept, so a program linked as
+    /// `tool.old` is invoked as `tool.old`. This is useful for
+    /// multi-call binaries.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// match args.invoked_as() {
+    ///     Some("compress") => println!("compressing"),
+    ///     Some("decompress") => println!("decompressing"),
+    ///     _ => eprintln!("unknown applet"),
+    /// }
+    /// ```
+    pub fn invoked_as(&self) -> Option<&str> {
+        let executable = self.nth(0)?;
+        let name = executable.rsplit(['/', '\\']).next().unwrap_or(executable);
+
+        match name.len().checked_sub(4) {
+            Some(at) if name.is_char_boundary(at) && name[at..].eq_ignore_ascii_case(".exe") => {
+                Some(&name[..at])
+            }
+            _ => Some(name),
+        }
+    }
+
+    /// Build an [`Args`] from the given arguments (including the
+    /// executable name), as returned by [`env::args_os`].
+    ///
+    /// Arguments that aren't valid unicode are converted lossily:
+    /// invalid sequences are replaced with `U+FFFD`.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// use valargs::Args;
+    ///
+    /// let args = Args::parse_os_from(&std::env::args_os().collect::<Vec<_>>());
+    /// ```
+    pub fn parse_os_from(args: &[OsString]) -> Args {
+        Args::parse_raw(
+            &args
+                .iter()
+                .map(|s| s.to_string_lossy().into_owned())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Get the value associated with the given option name, reading
+    /// it from stdin if the value is exactly `-`.
+    ///
+    /// Reading from stdin blocks until it is closed (end of file),
+    /// and the whole input is returned.
+    ///
+    /// #### Example:
+    ///
+    /// ```no_run
+    /// let args = valargs::parse();
+    ///
+    /// // `prog --input data.json` or `cat data.json | prog --input -`
+    /// if let Some(input) = args.option_value_or_stdin("input") {
+    ///     let input = input.expect("failed to read stdin");
+    ///     println!("got {} bytes", input.len());
+    /// }
+    /// ```
+    pub fn option_value_or_stdin(&self, option_name: &str) -> Option<io::Result<String>> {
+        self.option_value_or_read(option_name, io::stdin())
+    }
+
+    fn option_value_or_read(
+        &self,
+        option_name: &str,
+        mut reader: impl Read,
+    ) -> Option<io::Result<String>> {
+        match self.option_value(option_name)? {
+            "-" => {
+                let mut value = String::new();
+                Some(reader.read_to_string(&mut value).map(|_| value))
+            }
+            value => Some(Ok(value.to_string())),
+        }
+    }
+
+    /// Get a copy of these arguments with the same positional
+    /// arguments but only the given options, among the ones that
+    /// are present.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// // only forward the relevant options to the child process
+    /// let forwarded = args.select_options(&["color", "verbose"]);
+    /// ```
+    pub fn select_options(&self, option_names: &[&str]) -> Args {
+        let select = |name: &String| option_names.contains(&name.as_str());
+
+        Args {
+            args: self.args.clone(),
+            options: self
+                .options
+                .iter()
+                .filter(|(name, _)| select(name))
+                .map(|(name, values)| (name.to_owned(), values.to_owned()))
+                .collect(),
+            sources: self
+                .sources
+                .iter()
+                .filter(|(name, _)| select(name))
+                .map(|(name, source)| (name.to_owned(), source.to_owned()))
+                .collect(),
+            toggles: self.toggles.clone(),
+        }
+    }
+
+    /// Get a copy of these arguments without the subcommand name
+    /// cargo inserts when running a `cargo-<name>` plugin.
+    ///
+    /// Cargo invokes `cargo foo args...` as `cargo-foo foo args...`.
+    /// The `name` positional is removed only if it comes first and
+    /// the program was invoked as `cargo-<name>` (see
+    /// [`Args::invoked_as`]), so that indices are the same whether
+    /// the tool is run through cargo or directly.
+    pub fn strip_cargo_prefix(&self, name: &str) -> Args {
+        let mut args = self.clone();
+
+        let invoked_through_cargo = self
+            .invoked_as()
+            .and_then(|invoked_as| invoked_as.strip_prefix("cargo-"))
+            == Some(name);
+        if invoked_through_cargo && self.nth(1) == Some(name) {
+            args.args.remove(1);
+            for source in args.sources.values_mut() {
+                if let Source::CommandLine { argv_index } = source
+                    && *argv_index > 1
+                {
+                    *argv_index -= 1;
+                }
+            }
+        }
+
+        args
+    }
+
+    /// Remove all the options whose name starts with the given
+    /// prefix, returning how many were removed.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let mut args = valargs::parse();
+    ///
+    /// // consume the `--env.*` options, then forward the rest
+    /// let env_count = args.drop_options_with_prefix("env.");
+    /// ```
+    pub fn drop_options_with_prefix(&mut self, prefix: &str) -> usize {
+        let count = self.options.len();
+        self.options.retain(|name, _| !name.starts_with(prefix));
+        self.sources.retain(|name, _| !name.starts_with(prefix));
+        count - self.options.len()
+    }
+
+    /// Get the value associated with the given option name decoded
+    /// from base64 (standard alphabet).
+    ///
+    /// Padding (`=`) is optional, but when present it must bring the
+    /// length of the value to a multiple of 4.
+    ///
+    /// Requires the `base64` feature.
+    #[cfg(feature = "base64")]
+    pub fn option_base64(&self, option_name: &str) -> Option<Result<Vec<u8>, DecodeError>> {
+        self.option_value(option_name).map(base64::decode)
+    }
+
+    /// Get the value associated with the given option name split
+    /// into a key and a value on its first `=`.
+    ///
+    /// Returns `None` if the value contains no `=`. Any other `=`
+    /// is kept in the value, so `a=b=c` gives `("a", "b=c")`.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// // `prog --set color=red`
+    /// if let Some((key, value)) = args.option_kv("set") {
+    ///     println!("setting {} to {}", key, value);
+    /// }
+    /// ```
+    pub fn option_kv<'a>(&'a self, option_name: &str) -> Option<(&'a str, &'a str)> {
+        self.option_value(option_name)?.split_once('=')
+    }
+
+    /// Collect every occurrence of the given option into a map, each
+    /// value being split into a key and a value on its first `=`
+    /// (see [`Args::option_kv`]).
+    ///
+    /// Occurrences without a value or without `=` are skipped. When
+    /// a key is repeated, the last occurrence wins.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// // `prog --set a=1 --set b=2`
+    /// for (key, value) in args.options_kv_map("set") {
+    ///     println!("setting {} to {}", key, value);
+    /// }
+    /// ```
+    pub fn options_kv_map<'a>(&'a self, option_name: &str) -> HashMap<&'a str, &'a str> {
+        self.options
+            .get(option_name)
+            .into_iter()
+            .flatten()
+            .filter_map(|value| value.as_deref()?.split_once('='))
+            .collect()
+    }
+
+    /// Build an [`Args`] from the given arguments (including the
+    /// executable name), also returning warnings about the
+    /// non-fatal issues encountered instead of silently ignoring
+    /// them.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// use valargs::Args;
+    ///
+    /// let (args, warnings) = Args::parse_with_warnings(std::env::args());
+    /// for warning in warnings {
+    ///     eprintln!("warning: {}", warning);
+    /// }
+    /// ```
+    pub fn parse_with_warnings<I, S>(args: I) -> (Args, Vec<Warning>)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Args::parse_tokens(
+            &args.into_iter().map(Into::into).collect::<Vec<_>>(),
+            &ParseOptions::default(),
+        )
+    }
+
+    /// Get the value associated with the given option name, or ask
+    /// for it interactively if absent: `prompt` is printed to stdout
+    /// and a line is read from stdin.
+    ///
+    /// The trailing newline of the answer is removed. Fails with
+    /// [`io::ErrorKind::UnexpectedEof`] if stdin is closed before
+    /// anything is read.
+    ///
+    /// #### Example:
+    ///
+    /// ```no_run
+    /// let args = valargs::parse();
+    ///
+    /// let name = args.option_value_or_prompt("name", "name: ").unwrap();
+    /// println!("hello {} !!", name);
+    /// ```
+    pub fn option_value_or_prompt(&self, option_name: &str, prompt: &str) -> io::Result<String> {
+        self.option_value_or_prompt_with(option_name, prompt, io::stdin().lock(), io::stdout())
+    }
+
+    fn option_value_or_prompt_with(
+        &self,
+        option_name: &str,
+        prompt: &str,
+        mut reader: impl BufRead,
+        mut writer: impl Write,
+    ) -> io::Result<String> {
+        if let Some(value) = self.option_value(option_name) {
+            return Ok(value.to_string());
+        }
+
+        write!(writer, "{}", prompt)?;
+        writer.flush()?;
+
+        let mut answer = String::new();
+        if reader.read_line(&mut answer)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("no value given for {}", dashed(option_name)),
+            ));
+        }
+
+        let trimmed_len = answer.trim_end_matches(['\n', '\r']).len();
+        answer.truncate(trimmed_len);
+        Ok(answer)
+    }
+
+    /// Get the value associated with the given option name parsed
+    /// as a single CSV record.
+    ///
+    /// Only a minimal subset of CSV is supported: fields are
+    /// separated by commas, and a field wrapped in double quotes can
+    /// contain commas and escaped quotes (`""`). Whitespace around
+    /// fields is kept and the value is never split into several
+    /// records.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// // `prog --row 'a,"b,c",d'` gives ["a", "b,c", "d"]
+    /// if let Some(fields) = args.option_csv("row") {
+    ///     println!("{} fields", fields.len());
+    /// }
+    /// ```
+    pub fn option_csv(&self, option_name: &str) -> Option<Vec<String>> {
+        self.option_value(option_name).map(csv::split_record)
+    }
+
+    /// Get the value associated with the given option name parsed
+    /// with the given function.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), valargs::ParseError> {
+    /// use valargs::ParseOptions;
+    ///
+    /// let args = ParseOptions::new().parse(["prog", "--size", "800x600"])?;
+    ///
+    /// let size = args.option_value_parse_with("size", |value| {
+    ///     let (width, height) = value.split_once('x').ok_or("expected WxH")?;
+    ///     let width = width.parse::<u32>().map_err(|_| "invalid width")?;
+    ///     let height = height.parse::<u32>().map_err(|_| "invalid height")?;
+    ///     Ok::<_, &str>((width, height))
+    /// });
+    /// assert_eq!(Some(Ok((800, 600))), size);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn option_value_parse_with<T, E>(
+        &self,
+        option_name: &str,
+        f: impl FnOnce(&str) -> Result<T, E>,
+    ) -> Option<Result<T, E>> {
+        self.option_value(option_name).map(f)
+    }
+
+    /// Find the first option whose name matches the given predicate,
+    /// returning its name and value.
+    ///
+    /// Options are stored in a map and don't keep their command-line
+    /// order, so they are visited sorted by name: "first" means the
+    /// smallest matching name, not the first one given.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// if let Some((name, _)) = args.find_option(|name| name.starts_with("experimental-")) {
+    ///     eprintln!("warning: {} is experimental", name);
+    /// }
+    /// ```
+    pub fn find_option(&self, pred: impl Fn(&str) -> bool) -> Option<(&str, Option<&str>)> {
+        self.distinct_options()
+            .into_iter()
+            .find(|name| pred(name))
+            .map(|name| (name, self.option_value(name)))
+    }
+
+    /// Get the value of the `inline` option if present, otherwise
+    /// read the file named by the value of the `file` option.
+    ///
+    /// The inline value always takes precedence over the file. The
+    /// file contents are returned as is, including any trailing
+    /// newline.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// // `prog --token secret` or `prog --token-file token.txt`
+    /// match args.option_value_or_file("token", "token-file") {
+    ///     Some(Ok(token)) => println!("got a {} bytes token", token.len()),
+    ///     Some(Err(e)) => eprintln!("failed to read the token file: {}", e),
+    ///     None => eprintln!("no token given"),
+    /// }
+    /// ```
+    pub fn option_value_or_file(&self, inline: &str, file: &str) -> Option<io::Result<String>> {
+        match self.option_value(inline) {
+            Some(value) => Some(Ok(value.to_string())),
+            None => self.option_value(file).map(std::fs::read_to_string),
+        }
+    }
+
+    /// Get a copy of these arguments where the control characters of
+    /// positional arguments and option values are replaced with
+    /// their escaped representation (a tab becomes `\t`), as well as
+    /// the invisible Unicode format characters, such as zero-width
+    /// spaces and bidirectional controls (U+202E becomes
+    /// `\u{202e}`).
+    ///
+    /// This is meant for display only: the escaped values don't
+    /// round-trip and shouldn't be parsed or used again.
+    pub fn sanitized_for_display(&self) -> Args {
+        Args {
+            args: self.args.iter().map(|s| escape_control(s)).collect(),
+            options: self
+                .options
+                .iter()
+                .map(|(name, values)| {
+                    let values = values
+                        .iter()
+                        .map(|v| v.as_deref().map(escape_control))
+                        .collect();
+                    (name.to_owned(), values)
+                })
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Get the value associated with the given option name parsed
+    /// as a `YYYY-MM-DD` date, returned as the Unix timestamp (in
+    /// seconds) of its midnight UTC.
+    ///
+    /// Only this exact format is supported: a four-digit year and
+    /// two-digit month and day, without time or time zone.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// // `prog --since 2024-01-01`
+    /// if let Some(Ok(since)) = args.option_date_ymd("since") {
+    ///     println!("showing entries since {}", since);
+    /// }
+    /// ```
+    pub fn option_date_ymd(&self, option_name: &str) -> Option<Result<i64, DateParseError>> {
+        self.option_value(option_name).map(date::parse_ymd)
+    }
+
+    /// Get the number of distinct options whose name starts with
+    /// the given prefix.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// if args.count_options_with_prefix("env.") > 0 {
+    ///     println!("custom environment requested");
+    /// }
+    /// ```
+    pub fn count_options_with_prefix(&self, prefix: &str) -> usize {
+        self.options
+            .keys()
+            .filter(|name| name.starts_with(prefix))
+            .count()
+    }
+
+    /// Get the value associated with the given option name parsed
+    /// as `T`, falling back to `T::default()` if the option has no
+    /// value or if the value can't be parsed.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// let args = valargs::parse();
+    ///
+    /// let retries: u32 = args.option_parse_or_default("retries"); // 0 if absent
+    /// ```
+    pub fn option_parse_or_default<T: FromStr + Default>(&self, option_name: &str) -> T {
+        self.option_value(option_name)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// Start configuring how arguments are parsed, see
+    /// [`ParseOptions`] for every available setting and
+    /// [`ParseOptions::parse`] to parse arguments with them.
+    ///
+    /// #### Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), valargs::ParseError> {
+    /// use valargs::Args;
+    ///
+    /// let args = Args::builder()
+    ///     .default_value("color", "auto")
+    ///     .toggles(&['e', 'x'])
+    ///     .greedy("message")
+    ///     .value_separators(&['=', ':'])
+    ///     .numeric_shorthand("lines")
+    ///     .positional_prefix("@")
+    ///     .parse(std::env::args())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder() -> ParseOptions {
+        ParseOptions::new()
+    }
+
+    fn parse_raw(raw_args: &[String]) -> Args {
+        Args::parse_raw_with(raw_args, &ParseOptions::default())
+    }
+
+    fn parse_raw_with(raw_args: &[String], parse_options: &ParseOptions) -> Args {
+        Args::parse_tokens(raw_args, parse_options).0
+    }
+
+    fn try_parse_raw(raw_args: &[String]) -> Result<Args, ParseError> {
+        Args::try_parse_raw_with(raw_args, &ParseOptions::default())
+    }
+
+    fn try_parse_raw_with(
+        raw_args: &[String],
+        parse_options: &ParseOptions,
+    ) -> Result<Args, ParseError> {
+        let (args, warnings) = Args::parse_tokens(raw_args, parse_options);
+        let error = warnings.into_iter().find_map(|warning| match warning {
+            Warning::MalformedOption { token, index } => {
+                Some(ParseError::MalformedOption { token, index })
+            }
+            Warning::InvalidOptionName { name, index } => {
+                Some(ParseError::InvalidOptionName { name, index })
+            }
+            _ => None,
+        });
+        match error {
+            Some(error) => Err(error),
+            None => Ok(args),
+        }
+    }
+
+    /// Parse the given tokens leniently, also returning the warnings
+    /// about the non-fatal issues that were encountered.
+    fn parse_tokens(raw_args: &[String], parse_options: &ParseOptions) -> (Args, Vec<Warning>) {
+        let l = raw_args.len();
+
+        let mut args = Vec::new();
+        let mut options: HashMap<String, Vec<Option<String>>> = HashMap::new();
+        let mut sources = HashMap::new();
+        let mut toggles = HashMap::new();
+        let mut warnings = Vec::new();
+
+        let mut i = 0;
+        while i < l {
+            let token = raw_args[i].clone();
+
+            // Process the current token correctly whether it is a bundle
+            // of toggles (starting with "+" or "-"), a numeric shorthand
+            // (like "-5"), an option (starting with "--" or "-") or an
+            // argument.
+            let occurrence = if let Some((state, bundle)) = parse_options.parse_toggles(&token) {
+                for toggle in bundle.chars() {
+                    toggles.insert(toggle, state);
+                }
+                None
+            } else if let (Some(name), Some(number)) = (
+                &parse_options.numeric_shorthand,
+                parse_options.parse_numeric_shorthand(&token),
+            ) {
+                Some((name.to_owned(), Some(number.to_string()), i))
+            } else if token == "-" {
+                // A bare `-` conventionally stands for stdin or stdout.
+                args.push(token.to_owned());
+                None
+            } else if let Some(stripped) =
+                token.strip_prefix("--").or_else(|| token.strip_prefix("-"))
+            {
+                // Split the value attached to the option (`--option=value`)
+                // if any.
+                let (name, attached) =
+                    match stripped.split_once(|c| parse_options.value_separators.contains(&c)) {
+                        Some((name, value)) => (name, Some(value)),
+                        None => (stripped, None),
+                    };
+
+                if name.is_empty() || name.starts_with('-') {
+                    warnings.push(Warning::MalformedOption {
+                        token: token.to_owned(),
+                        index: i,
+                    });
+
+                    // Don't let an empty option name become a real key.
+                    if name.is_empty() {
+                        i += 1;
+                        continue;
+                    }
+                }
+
+                if name.contains(char::is_whitespace) {
+                    warnings.push(Warning::InvalidOptionName {
+                        name: name.to_owned(),
+                        index: i,
+                    });
+                }
+
+                // Check if the option has an associated value, either
+                // attached or made of the following value(s) (all of them
+                // if the option is greedy).
+                let value_count = if attached.is_some() {
+                    0
+                } else if parse_options.greedy.iter().any(|g| g == name) {
+                    raw_args[i + 1..]
+                        .iter()
+                        .take_while(|s| parse_options.is_value(s))
+                        .count()
+                } else {
+                    raw_args
+                        .get(i + 1)
+                        .filter(|s| parse_options.is_value(s))
+                        .map_or(0, |_| 1)
+                };
+                let param = match attached {
+                    Some(value) => Some(value.to_string()),
+                    None => (value_count > 0).then(|| raw_args[i + 1..=i + value_count].join(" ")),
+                };
+
+                // Skip the next tokens (the next iterations) if the option has
                 // an associated value.
-                if param.is_some() {
-                    i += 1;
+                i += value_count;
+
+                Some((name.to_string(), param, i - value_count))
+            } else {
+                let positional = match &parse_options.positional_prefix {
+                    Some(prefix) if i > 0 => token.strip_prefix(prefix.as_str()).unwrap_or(&token),
+                    _ => &token,
+                };
+                args.push(positional.to_string());
+                None
+            };
+
+            if let Some((name, param, argv_index)) = occurrence {
+                if let Some(value) = &param
+                    && looks_like_option(value)
+                {
+                    warnings.push(Warning::ValueLooksLikeOption {
+                        option: name.to_owned(),
+                        value: value.to_owned(),
+                        index: argv_index,
+                    });
+                }
+
+                let values = options.entry(name.to_owned()).or_default();
+                if values.last().is_some_and(|v| v.is_some()) {
+                    warnings.push(Warning::OptionOverwritten {
+                        option: name.to_owned(),
+                        index: argv_index,
+                    });
                 }
+                values.push(param);
+                sources.insert(name, Source::CommandLine { argv_index });
+            }
+            i += 1;
+        }
+
+        // Materialize default values for the options that weren't supplied.
+        for (name, value) in &parse_options.defaults {
+            if !options.contains_key(name) {
+                options.insert(name.to_owned(), vec![Some(value.to_owned())]);
+                sources.insert(name.to_owned(), Source::ConfigDefault);
+            }
+        }
+
+        (
+            Args {
+                args,
+                options,
+                sources,
+                toggles,
+            },
+            warnings,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_args() {
+        let args = Args::parse_raw(
+            &[
+                "exec",
+                "arg1",
+                "arg2",
+                "--option0",
+                "option0_value",
+                "arg3",
+                "-o",
+            ]
+            .map(|s| s.to_string()),
+        );
+        assert_eq!(Some("exec"), args.nth(0));
+        assert_eq!(Some("arg1"), args.nth(1));
+        assert_eq!(Some("arg2"), args.nth(2));
+        assert_eq!(Some("arg3"), args.nth(3));
+        assert_eq!(None, args.nth(4));
+
+        assert_eq!(Some("option0_value"), args.option_value("option0"));
+        assert!(args.has_option("o"));
+    }
+
+    #[test]
+    fn parse_args_with_defaults() {
+        let parse_options = ParseOptions::new()
+            .default_value("color", "auto")
+            .default_value("jobs", "1");
+        let args = Args::parse_raw_with(
+            &["exec", "--jobs", "4"].map(|s| s.to_string()),
+            &parse_options,
+        );
+        assert_eq!(Some("auto"), args.option_value("color"));
+        assert_eq!(Some("4"), args.option_value("jobs"));
+    }
+
+    #[test]
+    fn canonical_argv() {
+        let args = Args::parse_raw(
+            &["exec", "-v", "--zeta", "z", "arg1", "--alpha", "a", "arg2"].map(|s| s.to_string()),
+        );
+        let argv = args.to_canonical_argv();
+        assert_eq!(
+            vec!["exec", "arg1", "arg2", "--alpha=a", "-v", "--zeta=z"],
+            argv
+        );
+
+        let reparsed = Args::parse_raw(&argv);
+        assert_eq!(argv, reparsed.to_canonical_argv());
+
+        let args = Args::parse_raw(&["exec", "--offset=-5", "-d=--x", "-q"].map(|s| s.to_string()));
+        let argv = args.to_canonical_argv();
+        assert_eq!(vec!["exec", "-d=--x", "--offset=-5", "-q"], argv);
+
+        let reparsed = Args::parse_raw(&argv);
+        assert_eq!(Some("-5"), reparsed.option_value("offset"));
+        assert_eq!(Some("--x"), reparsed.option_value("d"));
+        assert!(reparsed.has_option("q"));
+        assert_eq!(argv, reparsed.to_canonical_argv());
+    }
+
+    #[test]
+    fn option_or_positional() {
+        let args = Args::parse_raw(
+            &["exec", "positional.txt", "--input", "option.txt"].map(|s| s.to_string()),
+        );
+        assert_eq!(Some("option.txt"), args.option_or_positional("input", 1));
+
+        let args = Args::parse_raw(&["exec", "positional.txt"].map(|s| s.to_string()));
+        assert_eq!(
+            Some("positional.txt"),
+            args.option_or_positional("input", 1)
+        );
+
+        let args = Args::parse_raw(&["exec"].map(|s| s.to_string()));
+        assert_eq!(None, args.option_or_positional("input", 1));
+    }
+
+    #[test]
+    fn parse_with_env_args() {
+        let raw_args = ["exec", "arg1", "--color", "always"].map(|s| s.to_string());
+        let lookup = |name: &str| {
+            (name == "MYTOOL_ARGS").then(|| "--color never -j 4 'env arg'".to_string())
+        };
+
+        let args =
+            parse_with_env_args_from(&raw_args, "MYTOOL_ARGS", &ParseOptions::default(), lookup)
+                .unwrap();
+        assert_eq!(Some("exec"), args.nth(0));
+        assert_eq!(Some("env arg"), args.nth(1));
+        assert_eq!(Some("arg1"), args.nth(2));
+        assert_eq!(Some("always"), args.option_value("color"));
+        assert_eq!(Some("4"), args.option_value("j"));
+
+        let args =
+            parse_with_env_args_from(&raw_args, "UNSET_ARGS", &ParseOptions::default(), lookup)
+                .unwrap();
+        assert_eq!(Some("arg1"), args.nth(1));
+        assert!(!args.has_option("j"));
+
+        let args =
+            parse_with_env_args_from(&raw_args, "EMPTY_ARGS", &ParseOptions::default(), |_| {
+                Some(String::new())
+            });
+        assert_eq!(Some("arg1"), args.unwrap().nth(1));
+
+        let error =
+            parse_with_env_args_from(&raw_args, "MYTOOL_ARGS", &ParseOptions::default(), |_| {
+                Some("--name 'oops".to_string())
+            })
+            .unwrap_err();
+        assert_eq!(
+            ParseError::UnterminatedQuote {
+                var: Some("MYTOOL_ARGS".to_string())
+            },
+            error
+        );
+        assert!(error.to_string().contains("MYTOOL_ARGS"));
+    }
+
+    #[test]
+    fn options_as_map_and_flags() {
+        let args = Args::parse_raw(
+            &["exec", "--color", "never", "-v", "--jobs", "4", "--dry-run"].map(|s| s.to_string()),
+        );
+
+        let map = args.options_as_map();
+        assert_eq!(2, map.len());
+        assert_eq!(Some("never"), map.get("color").map(|s| s.as_str()));
+        assert_eq!(Some("4"), map.get("jobs").map(|s| s.as_str()));
+
+        let mut flags = args.flags();
+        flags.sort();
+        assert_eq!(vec!["dry-run", "v"], flags);
+    }
+
+    #[test]
+    fn parse_toggles() {
+        let parse_options = ParseOptions::new().toggles(&['e', 'x', 'u']);
+        let args = Args::parse_raw_with(
+            &["exec", "+ex", "+y", "-x", "-v", "--name", "+u", "-ev"].map(|s| s.to_string()),
+            &parse_options,
+        );
+        assert_eq!(Some(true), args.toggle_state('e'));
+        assert_eq!(Some(false), args.toggle_state('x'));
+        assert_eq!(Some(true), args.toggle_state('u'));
+        assert_eq!(None, args.toggle_state('v'));
+        assert!(args.has_option("v"));
+        assert!(args.has_option("ev"));
+        assert_eq!(None, args.option_value("name"));
+        assert_eq!(Some("+y"), args.nth(1));
+
+        let args = Args::parse_raw(&["exec", "+e", "-e"].map(|s| s.to_string()));
+        assert_eq!(None, args.toggle_state('e'));
+        assert_eq!(Some("+e"), args.nth(1));
+        assert!(args.has_option("e"));
+    }
+
+    #[test]
+    fn without_executable() {
+        let args =
+            Args::parse_raw(&["exec", "build", "--release", "target", "-v"].map(|s| s.to_string()))
+                .without_executable();
+        assert_eq!(Some("build"), args.nth(0));
+        assert_eq!(None, args.nth(1));
+        assert_eq!(Some("target"), args.option_value("release"));
+        assert!(args.has_option("v"));
+        assert_eq!(
+            Some(Source::CommandLine { argv_index: 1 }),
+            args.option_source("release")
+        );
+        assert_eq!(
+            Some(Source::CommandLine { argv_index: 3 }),
+            args.option_source("v")
+        );
+    }
+
+    #[test]
+    fn check_exclusive() {
+        let group = ["quiet", "verbose"];
+
+        let args = Args::parse_raw(&["exec"].map(|s| s.to_string()));
+        assert_eq!(Ok(None), args.check_exclusive(&group));
+
+        let args = Args::parse_raw(&["exec", "--verbose"].map(|s| s.to_string()));
+        assert_eq!(Ok(Some("verbose")), args.check_exclusive(&group));
+
+        let args = Args::parse_raw(&["exec", "--verbose", "--quiet"].map(|s| s.to_string()));
+        let error = args.check_exclusive(&group).unwrap_err();
+        assert_eq!(vec!["quiet", "verbose"], error.options);
+        assert_eq!("conflicting options: --quiet, --verbose", error.to_string());
+
+        let args = Args::parse_raw(&["exec", "--verbose"].map(|s| s.to_string()));
+        assert_eq!(
+            Ok(Some("verbose")),
+            args.check_exclusive(&["verbose", "quiet", "verbose"])
+        );
+    }
+
+    #[test]
+    fn malformed_options() {
+        let raw_args =
+            ["exec", "---foo", "--=value", "--", "arg1", "-", "--ok"].map(|s| s.to_string());
+
+        let args = Args::parse_raw(&raw_args);
+        assert!(args.has_option("-foo"));
+        assert!(!args.has_option(""));
+        assert!(!args.has_option("=value"));
+        assert_eq!(Some("arg1"), args.nth(1));
+        assert_eq!(Some("-"), args.nth(2));
+        assert_eq!(None, args.nth(3));
+        assert!(args.has_option("ok"));
+
+        let (_, warnings) = Args::parse_with_warnings(raw_args.clone());
+        assert_eq!(
+            vec![(1, "---foo"), (2, "--=value"), (3, "--")],
+            warnings
+                .iter()
+                .map(|w| match w {
+                    Warning::MalformedOption { token, index } => (*index, token.as_str()),
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        );
+
+        for (token, problem) in [
+            ("---foo", "too many leading dashes"),
+            ("--=value", "missing option name before the attached value"),
+            ("--", "missing option name"),
+        ] {
+            let error = Args::try_parse_raw(&["exec", token].map(|s| s.to_string())).unwrap_err();
+            assert_eq!(
+                ParseError::MalformedOption {
+                    token: token.to_string(),
+                    index: 1
+                },
+                error
+            );
+            assert!(error.to_string().ends_with(problem));
+        }
+
+        assert!(Args::try_parse_raw(&["exec", "--ok", "-o"].map(|s| s.to_string())).is_ok());
+
+        let args = ParseOptions::new().parse(["exec", "cat", "-"]).unwrap();
+        assert_eq!(Some("-"), args.nth(2));
+        let args = Args::try_parse_raw(&["exec", "-", "--ok"].map(|s| s.to_string())).unwrap();
+        assert_eq!(Some("-"), args.nth(1));
+        assert!(args.has_option("ok"));
+    }
+
+    #[test]
+    fn require_exactly_one() {
+        let group = ["json", "yaml"];
+
+        let args = Args::parse_raw(&["exec"].map(|s| s.to_string()));
+        let error = args.require_exactly_one(&group).unwrap_err();
+        assert_eq!(
+            GroupError::Missing {
+                options: vec!["json".to_string(), "yaml".to_string()]
+            },
+            error
+        );
+        assert_eq!("expected one of: --json, --yaml", error.to_string());
+
+        let args = Args::parse_raw(&["exec", "--yaml"].map(|s| s.to_string()));
+        assert_eq!(Ok("yaml"), args.require_exactly_one(&group));
+
+        let args = Args::parse_raw(&["exec", "--yaml", "--json"].map(|s| s.to_string()));
+        assert!(matches!(
+            args.require_exactly_one(&group),
+            Err(GroupError::Conflict(_))
+        ));
+    }
+
+    #[test]
+    fn verbosity() {
+        let args = Args::parse_raw(&["exec", "-vvq"].map(|s| s.to_string()));
+        assert_eq!(1, args.verbosity());
+
+        let args = Args::parse_raw(&["exec", "--quiet", "--quiet"].map(|s| s.to_string()));
+        assert_eq!(-2, args.verbosity());
+
+        let args = Args::parse_raw(&["exec"].map(|s| s.to_string()));
+        assert_eq!(0, args.verbosity());
+
+        let args =
+            Args::parse_raw(&["exec", "-vv", "-v", "--verbose", "-v"].map(|s| s.to_string()));
+        assert_eq!(3, args.verbosity());
+        assert_eq!(2, args.option_count("v"));
+        assert_eq!(0, args.option_count("q"));
+
+        let config = VerbosityConfig::new()
+            .verbose('d', "debug")
+            .quiet('s', "silent");
+        let args = Args::parse_raw(&["exec", "-dd", "--silent", "-v"].map(|s| s.to_string()));
+        assert_eq!(1, args.verbosity_with(&config));
+    }
+
+    #[test]
+    fn parse_greedy_option() {
+        let parse_options = ParseOptions::new().greedy("message");
+        let args = Args::parse_raw_with(
+            &["exec", "--message", "hello", "there", "world", "-v", "arg1"].map(|s| s.to_string()),
+            &parse_options,
+        );
+        assert_eq!(Some("hello there world"), args.option_value("message"));
+        assert_eq!(Some("arg1"), args.option_value("v"));
+        assert_eq!(None, args.nth(1));
+
+        let args = Args::parse_raw_with(
+            &["exec", "arg1", "--message"].map(|s| s.to_string()),
+            &parse_options,
+        );
+        assert!(args.has_option("message"));
+        assert_eq!(None, args.option_value("message"));
+        assert_eq!(Some("arg1"), args.nth(1));
+
+        let raw_args = ["exec", "--message", "a", "b", "--", "c"];
+        let (args, warnings) = Args::parse_tokens(&raw_args.map(|s| s.to_string()), &parse_options);
+        assert_eq!(Some("a b"), args.option_value("message"));
+        assert_eq!(Some("c"), args.nth(1));
+        assert_eq!(
+            vec![Warning::MalformedOption {
+                token: "--".to_string(),
+                index: 4
+            }],
+            warnings
+        );
+        assert_eq!(
+            ParseError::MalformedOption {
+                token: "--".to_string(),
+                index: 4
+            },
+            parse_options.parse(raw_args).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn option_sources() {
+        let parse_options = ParseOptions::new()
+            .default_value("timeout", "30")
+            .default_value("jobs", "1");
+        let args = Args::parse_raw_with(
+            &["exec", "arg1", "--jobs", "4", "-v", "--jobs", "8"].map(|s| s.to_string()),
+            &parse_options,
+        );
+        assert_eq!(Some(Source::ConfigDefault), args.option_source("timeout"));
+        assert_eq!(
+            Some(Source::CommandLine { argv_index: 5 }),
+            args.option_source("jobs")
+        );
+        assert_eq!(
+            Some(Source::CommandLine { argv_index: 4 }),
+            args.option_source("v")
+        );
+        assert_eq!(None, args.option_source("color"));
+
+        let raw_args = ["exec", "--color", "always"].map(|s| s.to_string());
+        let args =
+            parse_with_env_args_from(&raw_args, "MYTOOL_ARGS", &ParseOptions::default(), |_| {
+                Some("--color never --jobs 2".to_string())
+            })
+            .unwrap();
+        assert_eq!(
+            Some(Source::CommandLine { argv_index: 1 }),
+            args.option_source("color")
+        );
+        assert_eq!(
+            Some(Source::Env {
+                var: "MYTOOL_ARGS".to_string()
+            }),
+            args.option_source("jobs")
+        );
+
+        let args = Args::parse_raw(&raw_args).prepend_str("--jobs 2").unwrap();
+        assert_eq!(
+            Some(Source::CommandLine { argv_index: 1 }),
+            args.option_source("jobs")
+        );
+        assert_eq!(
+            Some(Source::CommandLine { argv_index: 3 }),
+            args.option_source("color")
+        );
+    }
+
+    #[test]
+    fn expand_option_value() {
+        let lookup = |name: &str| (name == "HOME").then(|| "/home/cat".to_string());
+        assert_eq!("/home/cat/bin", expand_vars("$HOME/bin", lookup));
+        assert_eq!("/home/cat_bin", expand_vars("${HOME}_bin", lookup));
+        assert_eq!("/bin:", expand_vars("$UNDEFINED/bin:${UNDEFINED}", lookup));
+        assert_eq!("costs $5 or $", expand_vars("costs $5 or $", lookup));
+        assert_eq!("${HOME", expand_vars("${HOME", lookup));
+        assert_eq!("${}", expand_vars("${}", lookup));
+        assert_eq!(
+            "${1abc}",
+            expand_vars("${1abc}", |_| Some("set".to_string()))
+        );
+        assert_eq!(
+            "${A B}/bin",
+            expand_vars("${A B}/bin", |_| Some("set".to_string()))
+        );
+
+        let args = Args::parse_raw(&["exec", "--path", "plain/path"].map(|s| s.to_string()));
+        assert_eq!(
+            Some("plain/path".to_string()),
+            args.option_value_expanded("path")
+        );
+        assert_eq!(None, args.option_value_expanded("missing"));
+    }
+
+    #[test]
+    fn distinct_options() {
+        let args = Args::parse_raw(
+            &[
+                "exec", "-v", "--color", "never", "-v", "--color", "always", "-v",
+            ]
+            .map(|s| s.to_string()),
+        );
+        assert_eq!(vec!["color", "v"], args.distinct_options());
+        assert_eq!(3, args.option_count("v"));
+    }
+
+    #[test]
+    fn parts() {
+        let args = Args::parse_raw(
+            &[
+                "exec", "arg1", "--color", "never", "arg2", "-v", "--color", "always",
+            ]
+            .map(|s| s.to_string()),
+        );
+        let (positionals, options) = args.parts();
+        assert_eq!(vec!["exec", "arg1", "arg2"], positionals);
+        assert_eq!(vec![("color", Some("always")), ("v", None)], options);
+    }
 
-                options.insert(stripped.to_string(), param);
-            } else {
-                args.push(token);
-            }
-            i += 1;
-        }
+    #[test]
+    fn option_existing_path() {
+        let file = env::temp_dir().join(format!("valargs-existing-path-{}", std::process::id()));
+        std::fs::write(&file, "").unwrap();
+        let file_name = file.to_str().unwrap();
+
+        let args = Args::parse_raw(
+            &[
+                "exec",
+                "--input",
+                file_name,
+                "--output",
+                "/made/up/valargs/path",
+            ]
+            .map(|s| s.to_string()),
+        );
+        assert_eq!(file, args.option_existing_path("input").unwrap().unwrap());
+        assert_eq!(
+            io::ErrorKind::NotFound,
+            args.option_existing_path("output")
+                .unwrap()
+                .unwrap_err()
+                .kind()
+        );
+        assert!(args.option_existing_path("missing").is_none());
 
-        Args { args, options }
+        std::fs::remove_file(&file).unwrap();
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn map_round_trip() {
+        let args = Args::parse_raw(
+            &["exec", "arg1", "--set", "a=1", "-v", "--set", "b=2"].map(|s| s.to_string()),
+        );
+
+        let (positionals, options) = args.to_map();
+        assert_eq!(vec!["exec", "arg1"], positionals);
+        assert_eq!(Some(&vec!["a=1", "b=2"]), options.get("set"));
+        assert_eq!(Some(&vec![]), options.get("v"));
+
+        let (positionals, options) = args.clone().into_map();
+        let rebuilt = Args::from_parts(positionals, options);
+        assert_eq!(args.to_canonical_argv(), rebuilt.to_canonical_argv());
+        assert_eq!(Some("b=2"), rebuilt.option_value("set"));
+        assert!(rebuilt.has_option("v"));
+        assert_eq!(None, rebuilt.option_source("set"));
+
+        let args = Args::parse_raw(&["exec", "--color", "never", "--color"].map(|s| s.to_string()));
+        assert_eq!(None, args.option_value("color"));
+        let (positionals, options) = args.into_map();
+        let rebuilt = Args::from_parts(positionals, options);
+        assert_eq!(Some("never"), rebuilt.option_value("color"));
+        assert_eq!(1, rebuilt.option_count("color"));
+    }
 
     #[test]
-    fn parse_args() {
+    fn rename_option() {
+        let mut args = Args::parse_raw(
+            &["exec", "--colour", "never", "--color", "always"].map(|s| s.to_string()),
+        );
+        assert!(args.rename_option("colour", "color"));
+        assert!(!args.has_option("colour"));
+        assert_eq!(Some("never"), args.option_value("color"));
+        assert_eq!(
+            Some(Source::CommandLine { argv_index: 1 }),
+            args.option_source("color")
+        );
+
+        assert!(!args.rename_option("colour", "color"));
+        assert_eq!(Some("never"), args.option_value("color"));
+    }
+
+    #[test]
+    fn parse_attached_values() {
         let args = Args::parse_raw(
             &[
                 "exec",
+                "--output=out.txt",
+                "--define:key=value",
+                "-j=",
                 "arg1",
-                "arg2",
-                "--option0",
-                "option0_value",
-                "arg3",
-                "-o",
             ]
             .map(|s| s.to_string()),
         );
+        assert_eq!(Some("out.txt"), args.option_value("output"));
+        assert_eq!(Some("value"), args.option_value("define:key"));
+        assert_eq!(Some(""), args.option_value("j"));
+        assert_eq!(Some("arg1"), args.nth(1));
+
+        let parse_options = ParseOptions::new().value_separators(&['=', ':']);
+        let args = Args::parse_raw_with(
+            &[
+                "exec",
+                "--output:out.txt",
+                "--define:key=value",
+                "--map=a:b",
+                "-o:x",
+            ]
+            .map(|s| s.to_string()),
+            &parse_options,
+        );
+        assert_eq!(Some("out.txt"), args.option_value("output"));
+        assert_eq!(Some("key=value"), args.option_value("define"));
+        assert_eq!(Some("a:b"), args.option_value("map"));
+        assert_eq!(Some("x"), args.option_value("o"));
+
+        let args =
+            Args::parse_raw_with(&["exec", "--:value"].map(|s| s.to_string()), &parse_options);
+        assert_eq!(Vec::<&str>::new(), args.distinct_options());
+    }
+
+    #[test]
+    fn option_value_lines() {
+        let args = Args::parse_raw(
+            &["exec", "--script", "first\r\n\nthird\n", "--single", "line"].map(|s| s.to_string()),
+        );
+        assert_eq!(
+            Some(vec!["first", "", "third"]),
+            args.option_value_lines("script")
+        );
+        assert_eq!(Some(vec!["line"]), args.option_value_lines("single"));
+
+        let args =
+            Args::parse_raw(&["exec", "--text", "a\nb\r", "--empty="].map(|s| s.to_string()));
+        assert_eq!(Some(vec!["a", "b"]), args.option_value_lines("text"));
+        assert_eq!(Some(vec![]), args.option_value_lines("empty"));
+        assert_eq!(None, args.option_value_lines("missing"));
+    }
+
+    #[test]
+    fn positionals_from_end() {
+        let args = Args::parse_raw(&["exec", "a", "b", "dest"].map(|s| s.to_string()));
+        assert_eq!(Some("dest"), args.last_positional());
+        assert_eq!(Some("b"), args.nth_from_end(1));
+        assert_eq!(None, args.nth_from_end(3));
+
+        let (head, tail) = args.split_last_positionals(1).unwrap();
+        assert_eq!(["a", "b"], head);
+        assert_eq!(["dest"], tail);
+
+        let (head, tail) = args.split_last_positionals(3).unwrap();
+        assert!(head.is_empty());
+        assert_eq!(["a", "b", "dest"], tail);
+
+        assert_eq!(None, args.split_last_positionals(4));
+
+        let args = Args::parse_raw(&["exec"].map(|s| s.to_string()));
+        assert_eq!(None, args.last_positional());
+    }
+
+    #[test]
+    fn token_count() {
+        let args = Args::parse_raw_with(
+            &["exec", "arg1", "-v", "--jobs", "4", "-v", "arg2"].map(|s| s.to_string()),
+            &ParseOptions::new().default_value("color", "auto"),
+        );
+        assert_eq!(5, args.token_count());
+    }
+
+    #[test]
+    fn parse_numeric_shorthand() {
+        let parse_options = ParseOptions::new().numeric_shorthand("lines");
+
+        let args = Args::parse_raw_with(
+            &["exec", "-5", "file.txt"].map(|s| s.to_string()),
+            &parse_options,
+        );
+        assert_eq!(Some("5"), args.option_value("lines"));
+        assert_eq!(Some("file.txt"), args.nth(1));
+
+        let args = Args::parse_raw_with(
+            &["exec", "--offset", "-5"].map(|s| s.to_string()),
+            &parse_options,
+        );
+        assert_eq!(Some("-5"), args.option_value("offset"));
+        assert!(!args.has_option("lines"));
+
+        let args = Args::parse_raw_with(&["exec", "-5x"].map(|s| s.to_string()), &parse_options);
+        assert!(args.has_option("5x"));
+        assert!(!args.has_option("lines"));
+
+        let args = Args::parse_raw(&["exec", "-5"].map(|s| s.to_string()));
+        assert!(args.has_option("5"));
+    }
+
+    #[test]
+    fn invoked_as() {
+        let invoked_as = |executable: &str| {
+            Args::parse_raw(&[executable.to_string()])
+                .invoked_as()
+                .map(|s| s.to_string())
+        };
+        assert_eq!(
+            Some("compress".to_string()),
+            invoked_as("/usr/bin/compress")
+        );
+        assert_eq!(
+            Some("decompress".to_string()),
+            invoked_as(r"C:\Program Files\tools\decompress.exe")
+        );
+        assert_eq!(Some("tool".to_string()), invoked_as("tool.EXE"));
+        assert_eq!(Some("compress".to_string()), invoked_as("compress"));
+        assert_eq!(Some("tool.old".to_string()), invoked_as("./bin/tool.old"));
+
+        let args = Args::parse_raw(&[]);
+        assert_eq!(None, args.invoked_as());
+    }
+
+    #[test]
+    fn parse_os_from() {
+        let args =
+            Args::parse_os_from(&["exec", "arg1", "--color", "never", "-v"].map(OsString::from));
         assert_eq!(Some("exec"), args.nth(0));
         assert_eq!(Some("arg1"), args.nth(1));
-        assert_eq!(Some("arg2"), args.nth(2));
-        assert_eq!(Some("arg3"), args.nth(3));
-        assert_eq!(None, args.nth(4));
+        assert_eq!(Some("never"), args.option_value("color"));
+        assert!(args.has_option("v"));
+    }
 
-        assert_eq!(Some("option0_value"), args.option_value("option0"));
-        assert!(args.has_option("o"));
+    #[test]
+    fn option_value_or_stdin() {
+        let args =
+            Args::parse_raw(&["exec", "--input", "-", "--name", "cat", "-"].map(|s| s.to_string()));
+        assert_eq!(
+            "from stdin\n",
+            args.option_value_or_read("input", "from stdin\n".as_bytes())
+                .unwrap()
+                .unwrap()
+        );
+        assert_eq!(
+            "cat",
+            args.option_value_or_read("name", "unused".as_bytes())
+                .unwrap()
+                .unwrap()
+        );
+        assert!(
+            args.option_value_or_read("missing", "unused".as_bytes())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn select_options() {
+        let args = Args::parse_raw(
+            &["exec", "arg1", "--color", "never", "--jobs", "4", "-v"].map(|s| s.to_string()),
+        );
+        let selected = args.select_options(&["color", "v", "missing"]);
+        assert_eq!(Some("arg1"), selected.nth(1));
+        assert_eq!(vec!["color", "v"], selected.distinct_options());
+        assert_eq!(Some("never"), selected.option_value("color"));
+    }
+
+    #[test]
+    fn strip_cargo_prefix() {
+        let strip = |raw_args: &[&str]| {
+            Args::parse_raw(&raw_args.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+                .strip_cargo_prefix("foo")
+        };
+
+        let args = strip(&["/home/cat/.cargo/bin/cargo-foo", "foo", "build", "-v"]);
+        assert_eq!(Some("build"), args.nth(1));
+        assert_eq!(
+            Some(Source::CommandLine { argv_index: 2 }),
+            args.option_source("v")
+        );
+
+        let args = strip(&["cargo-foo", "build"]);
+        assert_eq!(Some("build"), args.nth(1));
+
+        let args = strip(&["foo", "foo", "build"]);
+        assert_eq!(Some("foo"), args.nth(1));
+        assert_eq!(Some("build"), args.nth(2));
+    }
+
+    #[test]
+    fn drop_options_with_prefix() {
+        let mut args = Args::parse_raw(
+            &[
+                "exec",
+                "--env.home",
+                "/home/cat",
+                "--env.user",
+                "cat",
+                "--color",
+                "never",
+            ]
+            .map(|s| s.to_string()),
+        );
+        assert_eq!(2, args.drop_options_with_prefix("env."));
+        assert_eq!(vec!["color"], args.distinct_options());
+        assert_eq!(0, args.drop_options_with_prefix("env."));
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn option_base64() {
+        let args = Args::parse_raw(
+            &[
+                "exec",
+                "--padded",
+                "aGVsbG8=",
+                "--unpadded",
+                "aGVsbG8",
+                "--invalid",
+                "aGV*bG8=",
+            ]
+            .map(|s| s.to_string()),
+        );
+        assert_eq!(Some(Ok(b"hello".to_vec())), args.option_base64("padded"));
+        assert_eq!(Some(Ok(b"hello".to_vec())), args.option_base64("unpadded"));
+        assert_eq!(
+            Some(Err(DecodeError::InvalidCharacter {
+                character: '*',
+                index: 3
+            })),
+            args.option_base64("invalid")
+        );
+        assert_eq!(None, args.option_base64("missing"));
+
+        let args = Args::parse_raw(&["exec", "--value", "aGVsbG8=="].map(|s| s.to_string()));
+        assert_eq!(
+            Some(Err(DecodeError::InvalidLength)),
+            args.option_base64("value")
+        );
+    }
+
+    #[test]
+    fn global_args() {
+        let cell = OnceLock::new();
+        let args = Args::parse_raw(&["exec", "--jobs", "4"].map(|s| s.to_string()));
+        assert_eq!(Ok(()), init_in(&cell, args.clone()));
+        assert_eq!(Some("4"), global_in(&cell).option_value("jobs"));
+        assert_eq!(Err(AlreadyInitialized), init_in(&cell, args.clone()));
+
+        let cell = OnceLock::new();
+        let _ = global_in(&cell);
+        assert_eq!(Err(AlreadyInitialized), init_in(&cell, args));
+    }
+
+    #[test]
+    fn option_kv() {
+        let args = Args::parse_raw(
+            &[
+                "exec",
+                "--set",
+                "color=red",
+                "--define",
+                "a=b=c",
+                "--name",
+                "cat",
+            ]
+            .map(|s| s.to_string()),
+        );
+        assert_eq!(Some(("color", "red")), args.option_kv("set"));
+        assert_eq!(Some(("a", "b=c")), args.option_kv("define"));
+        assert_eq!(None, args.option_kv("name"));
+    }
+
+    #[test]
+    fn options_kv_map() {
+        let args = Args::parse_raw(
+            &[
+                "exec",
+                "--set",
+                "a=1",
+                "--set",
+                "malformed",
+                "--set",
+                "b=2",
+                "--set",
+            ]
+            .map(|s| s.to_string()),
+        );
+        assert_eq!(
+            HashMap::from([("a", "1"), ("b", "2")]),
+            args.options_kv_map("set")
+        );
+        assert!(args.options_kv_map("missing").is_empty());
+    }
+
+    #[test]
+    fn nth_from_end() {
+        let args = Args::parse_raw(&["exec", "a", "b", "c"].map(|s| s.to_string()));
+        assert_eq!(Some("c"), args.nth_from_end(0));
+        assert_eq!(Some("b"), args.nth_from_end(1));
+        assert_eq!(Some("a"), args.nth_from_end(2));
+        assert_eq!(None, args.nth_from_end(3));
+        assert_eq!(None, args.nth_from_end(usize::MAX));
+    }
+
+    #[test]
+    fn parse_with_warnings() {
+        let (args, warnings) = Args::parse_with_warnings([
+            "exec",
+            "--",
+            "--output=--verbose",
+            "--color",
+            "never",
+            "--color",
+            "always",
+            "-v",
+            "-v",
+            "--offset=-5",
+        ]);
+        assert_eq!(Some("always"), args.option_value("color"));
+        assert_eq!(
+            vec![
+                Warning::MalformedOption {
+                    token: "--".to_string(),
+                    index: 1
+                },
+                Warning::ValueLooksLikeOption {
+                    option: "output".to_string(),
+                    value: "--verbose".to_string(),
+                    index: 2
+                },
+                Warning::OptionOverwritten {
+                    option: "color".to_string(),
+                    index: 5
+                },
+            ],
+            warnings
+        );
+        assert_eq!(
+            "option --color at position 5 overwrites a previous value",
+            warnings[2].to_string()
+        );
+
+        let (_, warnings) = Args::parse_with_warnings(["exec", "arg1", "--jobs", "4"]);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn option_value_or_prompt() {
+        let args = Args::parse_raw(&["exec", "--name", "cat"].map(|s| s.to_string()));
+
+        let mut output = Vec::new();
+        let value =
+            args.option_value_or_prompt_with("name", "name: ", "unused\n".as_bytes(), &mut output);
+        assert_eq!("cat", value.unwrap());
+        assert!(output.is_empty());
+
+        let mut output = Vec::new();
+        let value =
+            args.option_value_or_prompt_with("food", "food: ", "fish\r\n".as_bytes(), &mut output);
+        assert_eq!("fish", value.unwrap());
+        assert_eq!(b"food: ", output.as_slice());
+
+        let error = args
+            .option_value_or_prompt_with("food", "food: ", "".as_bytes(), Vec::new())
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, error.kind());
+    }
+
+    #[test]
+    fn invalid_option_names() {
+        let error = Args::try_parse_raw(&["exec", "--foo bar"].map(|s| s.to_string())).unwrap_err();
+        assert_eq!(
+            ParseError::InvalidOptionName {
+                name: "foo bar".to_string(),
+                index: 1
+            },
+            error
+        );
+        assert!(error.to_string().contains("whitespace"));
+
+        let error =
+            Args::try_parse_raw(&["exec", "--tab\there=x"].map(|s| s.to_string())).unwrap_err();
+        assert!(matches!(error, ParseError::InvalidOptionName { .. }));
+
+        let error = Args::try_parse_raw(&["exec", "--"].map(|s| s.to_string())).unwrap_err();
+        assert!(matches!(error, ParseError::MalformedOption { .. }));
+
+        let args = Args::parse_raw(&["exec", "--foo bar"].map(|s| s.to_string()));
+        assert!(args.has_option("foo bar"));
+    }
+
+    #[test]
+    fn option_csv() {
+        let args = Args::parse_raw(
+            &[
+                "exec",
+                "--simple",
+                "a,b,,c",
+                "--quoted",
+                r#"a,"b,c",d"#,
+                "--escaped",
+                r#""say ""hi""",x"#,
+            ]
+            .map(|s| s.to_string()),
+        );
+        assert_eq!(
+            ["a", "b", "", "c"],
+            args.option_csv("simple").unwrap().as_slice()
+        );
+        assert_eq!(
+            ["a", "b,c", "d"],
+            args.option_csv("quoted").unwrap().as_slice()
+        );
+        assert_eq!(
+            [r#"say "hi""#, "x"],
+            args.option_csv("escaped").unwrap().as_slice()
+        );
+        assert_eq!(None, args.option_csv("missing"));
+    }
+
+    #[test]
+    fn parse_positional_prefix() {
+        let parse_options = ParseOptions::new().positional_prefix("+");
+        let args = Args::parse_raw_with(
+            &["+exec", "+first", "second", "+third", "--name", "+value"].map(|s| s.to_string()),
+            &parse_options,
+        );
+        assert_eq!(Some("+exec"), args.nth(0));
+        assert_eq!(Some("first"), args.nth(1));
+        assert_eq!(Some("second"), args.nth(2));
+        assert_eq!(Some("third"), args.nth(3));
+        assert_eq!(Some("+value"), args.option_value("name"));
+    }
+
+    #[test]
+    fn option_value_parse_with() {
+        let parse_size = |value: &str| -> Result<(u32, u32), String> {
+            let (width, height) = value.split_once('x').ok_or("expected WxH")?;
+            Ok((
+                width.parse().map_err(|e| format!("invalid width: {}", e))?,
+                height
+                    .parse()
+                    .map_err(|e| format!("invalid height: {}", e))?,
+            ))
+        };
+
+        let args = Args::parse_raw(
+            &["exec", "--size", "800x600", "--bad-size", "800xtall"].map(|s| s.to_string()),
+        );
+        assert_eq!(
+            Some(Ok((800, 600))),
+            args.option_value_parse_with("size", parse_size)
+        );
+        assert!(matches!(
+            args.option_value_parse_with("bad-size", parse_size),
+            Some(Err(_))
+        ));
+        assert_eq!(None, args.option_value_parse_with("missing", parse_size));
+    }
+
+    #[test]
+    fn find_option() {
+        let args = Args::parse_raw(
+            &["exec", "--2fa", "--color", "never", "--beta"].map(|s| s.to_string()),
+        );
+        let starts_with_letter = |name: &str| name.starts_with(|c: char| c.is_alphabetic());
+        assert_eq!(Some(("beta", None)), args.find_option(starts_with_letter));
+        assert_eq!(
+            Some(("color", Some("never"))),
+            args.find_option(|name| name.starts_with('c'))
+        );
+        assert_eq!(None, args.find_option(|name| name.is_empty()));
+    }
+
+    #[test]
+    fn option_value_or_file() {
+        let file = env::temp_dir().join(format!("valargs-value-or-file-{}", std::process::id()));
+        std::fs::write(&file, "from file").unwrap();
+        let file_name = file.to_str().unwrap();
+
+        let args = Args::parse_raw(&["exec", "--token-file", file_name].map(|s| s.to_string()));
+        assert_eq!(
+            "from file",
+            args.option_value_or_file("token", "token-file")
+                .unwrap()
+                .unwrap()
+        );
+
+        let args = Args::parse_raw(
+            &["exec", "--token", "inline", "--token-file", file_name].map(|s| s.to_string()),
+        );
+        assert_eq!(
+            "inline",
+            args.option_value_or_file("token", "token-file")
+                .unwrap()
+                .unwrap()
+        );
+
+        let args = Args::parse_raw(
+            &["exec", "--token-file", "/made/up/valargs/path"].map(|s| s.to_string()),
+        );
+        assert!(
+            args.option_value_or_file("token", "token-file")
+                .unwrap()
+                .is_err()
+        );
+        assert!(
+            args.option_value_or_file("missing", "missing-file")
+                .is_none()
+        );
+
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn sanitized_for_display() {
+        let args = Args::from_parts(
+            vec![
+                "exec".to_string(),
+                "tab\there".to_string(),
+                "evil\u{202e}txt.exe".to_string(),
+                "zero\u{200b}width".to_string(),
+                "caf\u{e9}".to_string(),
+            ],
+            HashMap::from([(
+                "message".to_string(),
+                vec!["line\nbreak\u{1b}[0m".to_string()],
+            )]),
+        )
+        .sanitized_for_display();
+        assert_eq!(Some(r"tab\there"), args.nth(1));
+        assert_eq!(Some(r"evil\u{202e}txt.exe"), args.nth(2));
+        assert_eq!(Some(r"zero\u{200b}width"), args.nth(3));
+        assert_eq!(Some("caf\u{e9}"), args.nth(4));
+        assert_eq!(Some(r"line\nbreak\u{1b}[0m"), args.option_value("message"));
+    }
+
+    #[test]
+    fn option_date_ymd() {
+        let args = Args::parse_raw(
+            &[
+                "exec",
+                "--epoch",
+                "1970-01-01",
+                "--since",
+                "2024-01-01",
+                "--leap-day",
+                "2024-02-29",
+                "--not-leap",
+                "2023-02-29",
+                "--month",
+                "2024-13-01",
+                "--format",
+                "2024/01/01",
+            ]
+            .map(|s| s.to_string()),
+        );
+        assert_eq!(Some(Ok(0)), args.option_date_ymd("epoch"));
+        assert_eq!(Some(Ok(1704067200)), args.option_date_ymd("since"));
+        assert_eq!(Some(Ok(1709164800)), args.option_date_ymd("leap-day"));
+        assert_eq!(
+            Some(Err(DateParseError::InvalidDate)),
+            args.option_date_ymd("not-leap")
+        );
+        assert_eq!(
+            Some(Err(DateParseError::InvalidDate)),
+            args.option_date_ymd("month")
+        );
+        assert_eq!(
+            Some(Err(DateParseError::InvalidFormat)),
+            args.option_date_ymd("format")
+        );
+    }
+
+    #[test]
+    fn count_options_with_prefix() {
+        let args = Args::parse_raw(
+            &[
+                "exec",
+                "--env.home",
+                "/home/cat",
+                "--env.user",
+                "cat",
+                "--environment",
+                "prod",
+            ]
+            .map(|s| s.to_string()),
+        );
+        assert_eq!(2, args.count_options_with_prefix("env."));
+        assert_eq!(0, args.count_options_with_prefix("color"));
+    }
+
+    #[test]
+    fn option_parse_or_default() {
+        let args =
+            Args::parse_raw(&["exec", "--jobs", "4", "--retries", "many"].map(|s| s.to_string()));
+        assert_eq!(4, args.option_parse_or_default::<u32>("jobs"));
+        assert_eq!(0, args.option_parse_or_default::<u32>("timeout"));
+        assert_eq!(0, args.option_parse_or_default::<u32>("retries"));
+    }
+
+    #[test]
+    fn builder() {
+        let args = Args::builder()
+            .default_value("color", "auto")
+            .toggles(&['e', 'x'])
+            .greedy("message")
+            .value_separators(&['=', ':'])
+            .numeric_shorthand("lines")
+            .positional_prefix("@")
+            .parse([
+                "exec",
+                "@file.txt",
+                "-20",
+                "+e",
+                "--output:out.txt",
+                "--message",
+                "hello",
+                "world",
+            ])
+            .unwrap();
+        assert_eq!(Some("file.txt"), args.nth(1));
+        assert_eq!(Some("20"), args.option_value("lines"));
+        assert_eq!(Some(true), args.toggle_state('e'));
+        assert_eq!(Some("out.txt"), args.option_value("output"));
+        assert_eq!(Some("hello world"), args.option_value("message"));
+        assert_eq!(Some("auto"), args.option_value("color"));
+
+        let error = Args::builder().parse(["exec", "---foo"]).unwrap_err();
+        assert!(matches!(error, ParseError::MalformedOption { .. }));
+    }
+
+    #[test]
+    fn prepend_str_overrides_defaults() {
+        let args = ParseOptions::new()
+            .default_value("color", "auto")
+            .default_value("jobs", "1")
+            .parse(["exec", "--jobs", "4"])
+            .unwrap()
+            .prepend_str("--color never --jobs 2")
+            .unwrap();
+        assert_eq!(Some("never"), args.option_value("color"));
+        assert_eq!(1, args.option_count("color"));
+        assert_eq!(
+            Some(Source::CommandLine { argv_index: 1 }),
+            args.option_source("color")
+        );
+        assert_eq!(Some("4"), args.option_value("jobs"));
+
+        let args = ParseOptions::new()
+            .default_value("color", "auto")
+            .parse(["exec"])
+            .unwrap()
+            .prepend_str("--jobs 2")
+            .unwrap();
+        assert_eq!(Some("auto"), args.option_value("color"));
+        assert_eq!(Some(Source::ConfigDefault), args.option_source("color"));
+    }
+
+    #[test]
+    fn prepend_str_with() {
+        let parse_options = ParseOptions::new()
+            .value_separators(&[':'])
+            .toggles(&['x'])
+            .default_value("jobs", "1");
+        let args = parse_options
+            .parse(["exec", "--color:always", "arg1"])
+            .unwrap()
+            .prepend_str_with("--jobs:4 +x --color:never env", &parse_options)
+            .unwrap();
+        assert_eq!(Some("4"), args.option_value("jobs"));
+        assert_eq!(1, args.option_count("jobs"));
+        assert_eq!(Some("always"), args.option_value("color"));
+        assert_eq!(Some(true), args.toggle_state('x'));
+        assert_eq!(Some("env"), args.nth(1));
+        assert_eq!(Some("arg1"), args.nth(2));
+        assert_eq!(
+            Some(Source::CommandLine { argv_index: 1 }),
+            args.option_source("jobs")
+        );
+        assert_eq!(
+            Some(Source::CommandLine { argv_index: 5 }),
+            args.option_source("color")
+        );
+
+        let raw_args = ["exec", "--color:always"].map(|s| s.to_string());
+        let args = parse_with_env_args_from(&raw_args, "MYTOOL_ARGS", &parse_options, |_| {
+            Some("--jobs:2".to_string())
+        })
+        .unwrap();
+        assert_eq!(Some("2"), args.option_value("jobs"));
+        assert_eq!(
+            Some(Source::Env {
+                var: "MYTOOL_ARGS".to_string()
+            }),
+            args.option_source("jobs")
+        );
+
+        let error = Args::builder()
+            .parse(["exec"])
+            .unwrap()
+            .prepend(
+                "---foo",
+                Some("MYTOOL_ARGS"),
+                &ParseOptions::default(),
+                true,
+            )
+            .unwrap_err();
+        assert!(matches!(error, ParseError::MalformedOption { .. }));
     }
 }
@@ -0,0 +1,109 @@
+//! A small shell-like splitter used to turn a single string
+//! into a list of arguments.
+
+use crate::ParseError;
+
+/// Split the given string into arguments the way a POSIX shell
+/// would, without performing any expansion.
+///
+/// - Unquoted whitespace separates arguments.
+/// - Single quotes preserve everything up to the closing quote.
+/// - Double quotes preserve everything up to the closing quote,
+///   except that `\"` and `\\` are unescaped.
+/// - Outside of quotes, a backslash escapes the next character.
+pub(crate) fn split(s: &str) -> Result<Vec<String>, ParseError> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    // Whether a word has been started, so that `''` yields an
+    // empty argument instead of nothing.
+    let mut in_word = false;
+
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(ParseError::UnterminatedQuote { var: None }),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(ParseError::UnterminatedQuote { var: None }),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(ParseError::UnterminatedQuote { var: None }),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => word.push(c),
+                    None => return Err(ParseError::TrailingBackslash { var: None }),
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_words() {
+        assert_eq!(
+            Ok(vec![
+                "--color=never".to_string(),
+                "-j4".to_string(),
+                "a b".to_string(),
+                "c\"d".to_string(),
+                "e f".to_string(),
+                "".to_string(),
+            ]),
+            split(r#"  --color=never -j4 'a b' "c\"d" e\ f '' "#)
+        );
+    }
+
+    #[test]
+    fn split_malformed() {
+        assert_eq!(
+            Err(ParseError::UnterminatedQuote { var: None }),
+            split("--name 'oops")
+        );
+        assert_eq!(
+            Err(ParseError::TrailingBackslash { var: None }),
+            split("--name oops\\")
+        );
+    }
+}